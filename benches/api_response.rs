@@ -0,0 +1,56 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use freeswitch_esl::bench::decode_api_response;
+
+/// Counts allocations, to show how many each route makes per api response
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const FRAME: &[u8] = b"Content-Type: api/response\nContent-Length: 23\n\n+OK UP 0 years, 0 days\n";
+
+fn allocations(fast_path: bool) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(decode_api_response(FRAME, fast_path));
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn bench_api_response(c: &mut Criterion) {
+    println!(
+        "allocations per api response: parsed headers {}, fast path {}",
+        allocations(false),
+        allocations(true)
+    );
+    let mut group = c.benchmark_group("api_response");
+    group.bench_function("parsed_headers", |b| {
+        b.iter(|| decode_api_response(black_box(FRAME), false))
+    });
+    group.bench_function("fast_path", |b| {
+        b.iter(|| decode_api_response(black_box(FRAME), true))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_api_response);
+criterion_main!(benches);
@@ -0,0 +1,86 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use freeswitch_esl::bench::parse_header;
+use indexmap::IndexMap;
+use serde_json::Value;
+
+/// Counts allocations, to show how many each parser makes per header block
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+type Headers = Result<IndexMap<String, Value>, std::io::Error>;
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Header parsing before it borrowed the block and pre-sized the map
+fn parse_header_copied(src: &[u8]) -> Headers {
+    let data = match std::str::from_utf8(src) {
+        Ok(data) => data.to_string(),
+        Err(_) => String::from_utf8_lossy(src).to_string(),
+    };
+    let mut hash = IndexMap::new();
+    for line in data.split('\n') {
+        if let Some((key, val)) = line.split_once(':') {
+            let key = key.trim();
+            let val = val.trim();
+            hash.insert(key.to_string(), serde_json::json!(val.to_string()));
+        }
+    }
+    Ok(hash)
+}
+
+/// Header block of a `CHANNEL_EXECUTE_COMPLETE` event with 60 lines
+fn header_block() -> Vec<u8> {
+    let mut block = String::from("Event-Name: CHANNEL_EXECUTE_COMPLETE\n");
+    block.push_str("Channel-Name: sofia/internal/1000@172.31.32.63:5060\n");
+    for i in 2..60 {
+        block.push_str(&format!("variable_header_{i}: value%20number%20{i}\n"));
+    }
+    block.pop();
+    block.into_bytes()
+}
+
+fn allocations(parse: fn(&[u8]) -> Headers) -> usize {
+    let block = header_block();
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(parse(&block).unwrap());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn bench_parse_header(c: &mut Criterion) {
+    println!(
+        "allocations per header block: copied {}, borrowed {}",
+        allocations(parse_header_copied),
+        allocations(parse_header)
+    );
+    let block = header_block();
+    let mut group = c.benchmark_group("parse_header");
+    group.bench_function("copied", |b| {
+        b.iter(|| parse_header_copied(black_box(&block)))
+    });
+    group.bench_function("borrowed", |b| b.iter(|| parse_header(black_box(&block))));
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse_header);
+criterion_main!(benches);
@@ -0,0 +1,494 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde_json::Value;
+use tracing::trace;
+
+use crate::event::field_string;
+use crate::{EslConnection, EslError, Event, ResponseCode};
+
+/// Body content reported by `uuid_*` commands on failure, sometimes behind `+OK`
+const UUID_API_FAILURES: &[&str] = &["-ERR", "-USAGE", "No such channel", "INVALID"];
+
+/// Channel variable holding the key of [`EslConnection::bgapi_originate_idempotent`],
+/// read back to recognise a leg dialed for the same key
+pub const IDEMPOTENCY_KEY_VARIABLE: &str = "esl_idempotency_key";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Playback control command for [`EslConnection::uuid_fileman`]
+pub enum FileManCommand {
+    /// Toggles pause of the playback
+    Pause,
+    /// Restarts the playback from the beginning
+    Restart,
+    /// Seeks forward or backward by milliseconds
+    Seek(i64),
+    /// Changes the playback speed by steps
+    Speed(i8),
+    /// Changes the playback volume by steps
+    Volume(i8),
+    /// Stops the playback and truncates the file
+    Truncate,
+}
+
+impl fmt::Display for FileManCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Pause => write!(f, "pause"),
+            Self::Restart => write!(f, "restart"),
+            Self::Seek(millis) => write!(f, "seek:{:+}", millis),
+            Self::Speed(step) => write!(f, "speed:{:+}", step),
+            Self::Volume(step) => write!(f, "volume:{:+}", step),
+            Self::Truncate => write!(f, "truncate"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Device event for [`EslConnection::uuid_phone_event`]
+pub enum PhoneEvent {
+    /// Tells the phone the call is active
+    Talk,
+    /// Tells the phone the call is on hold
+    Hold,
+}
+
+impl fmt::Display for PhoneEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Talk => write!(f, "talk"),
+            Self::Hold => write!(f, "hold"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Log level of freeswitch, from the most to the least severe
+pub enum LogLevel {
+    /// Level `0`, always logged
+    Console,
+    /// Level `1`
+    Alert,
+    /// Level `2`
+    Crit,
+    /// Level `3`
+    Err,
+    /// Level `4`
+    Warning,
+    /// Level `5`
+    Notice,
+    /// Level `6`
+    Info,
+    /// Level `7`
+    Debug,
+}
+
+const LOG_LEVELS: [LogLevel; 8] = [
+    LogLevel::Console,
+    LogLevel::Alert,
+    LogLevel::Crit,
+    LogLevel::Err,
+    LogLevel::Warning,
+    LogLevel::Notice,
+    LogLevel::Info,
+    LogLevel::Debug,
+];
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Console => write!(f, "console"),
+            Self::Alert => write!(f, "alert"),
+            Self::Crit => write!(f, "crit"),
+            Self::Err => write!(f, "err"),
+            Self::Warning => write!(f, "warning"),
+            Self::Notice => write!(f, "notice"),
+            Self::Info => write!(f, "info"),
+            Self::Debug => write!(f, "debug"),
+        }
+    }
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = EslError;
+    /// Parses a level name like `DEBUG` or its number like `7`
+    fn from_str(level: &str) -> Result<Self, EslError> {
+        let level = level.trim();
+        LOG_LEVELS
+            .into_iter()
+            .enumerate()
+            .find(|(number, name)| {
+                level == number.to_string() || level.eq_ignore_ascii_case(&name.to_string())
+            })
+            .map(|(_, level)| level)
+            .ok_or_else(|| EslError::InvalidArgument(format!("unknown log level {:?}", level)))
+    }
+}
+
+/// Returns `ApiError` when the body of an api reply starts with one of `failures`
+pub(crate) fn check_api_body(body: String, failures: &[&str]) -> Result<String, EslError> {
+    let text = body.trim();
+    match failures.iter().find(|failure| text.starts_with(*failure)) {
+        Some(failure) => {
+            let reason = text[failure.len()..].trim_start_matches(':').trim();
+            let reason = if reason.is_empty() { text } else { reason };
+            Err(EslError::ApiError(reason.to_string()))
+        }
+        None => Ok(body),
+    }
+}
+
+impl EslConnection {
+    /// sends api command of the `uuid_*` family, checking its body for failures
+    pub(crate) async fn uuid_api(&self, command: &str) -> Result<String, EslError> {
+        let body = self.api(command).await?;
+        check_api_body(body, UUID_API_FAILURES)
+    }
+
+    /// Starts `originate` as a background job without waiting for the result.
+    ///
+    /// Returns the `Job-UUID`, which is also used as `origination_uuid` of the
+    /// new leg so the call can be cancelled with [`EslConnection::cancel_originate`].
+    /// `args` must not set `origination_uuid` itself.
+    pub async fn bgapi_originate(&self, args: &str) -> Result<String, EslError> {
+        let job_uuid = uuid::Uuid::new_v4().to_string();
+        self.bgapi_originate_as(&job_uuid, "", args).await
+    }
+
+    /// Starts `originate` like [`EslConnection::bgapi_originate`], at most once per `key`.
+    ///
+    /// The uuid of the new leg is derived from `key`, which is also stored in the
+    /// [`IDEMPOTENCY_KEY_VARIABLE`] channel variable. If the channel with that uuid
+    /// carries the key, e.g. when retrying after a reconnect, nothing is dialed and
+    /// `None` is returned. Otherwise the `Job-UUID`, equal to the leg uuid, is returned.
+    /// A channel with that uuid but another key is an `InvalidArgument`, as is an empty
+    /// key or one with whitespace, control characters, quotes or any of `,{}=`.
+    ///
+    /// Only live channels are seen: a retry after the first call ended or before
+    /// its leg was created dials again.
+    pub async fn bgapi_originate_idempotent(
+        &self,
+        args: &str,
+        key: &str,
+    ) -> Result<Option<String>, EslError> {
+        // the key is the value of `{name=key,}` on the originate command line
+        let unsafe_char = |c: char| c.is_whitespace() || c.is_control() || ",{}='\"".contains(c);
+        if key.is_empty() || key.contains(unsafe_char) {
+            return Err(EslError::InvalidArgument(format!(
+                "idempotency key {:?} can't be a channel variable",
+                key
+            )));
+        }
+        let uuid = idempotency_uuid(key);
+        let getvar = format!("uuid_getvar {} {}", uuid, IDEMPOTENCY_KEY_VARIABLE);
+        match self.api(&getvar).await {
+            Ok(value) if value.trim() == key => {
+                trace!("originate with key {} already active as {}", key, uuid);
+                return Ok(None);
+            }
+            Ok(value) => {
+                return Err(EslError::InvalidArgument(format!(
+                    "uuid {} of idempotency key {:?} is used by a channel with key {:?}",
+                    uuid,
+                    key,
+                    value.trim()
+                )))
+            }
+            Err(e) if e.response_code() == Some(ResponseCode::NoSuchChannel) => {}
+            Err(e) => return Err(e),
+        }
+        let variables = format!("{}={},", IDEMPOTENCY_KEY_VARIABLE, key);
+        Ok(Some(
+            self.bgapi_originate_as(&uuid, &variables, args).await?,
+        ))
+    }
+
+    pub(crate) async fn bgapi_originate_as(
+        &self,
+        job_uuid: &str,
+        variables: &str,
+        args: &str,
+    ) -> Result<String, EslError> {
+        let args = with_origination_uuid(args, variables, job_uuid);
+        let response = self
+            .send_recv(format!("bgapi originate {}\nJob-UUID: {}", args, job_uuid).as_bytes())
+            .await?;
+        if let Some(error) = response.reply_text().and_then(|t| t.strip_prefix("-ERR")) {
+            return Err(EslError::ApiError(error.trim().to_string()));
+        }
+        Ok(job_uuid.to_string())
+    }
+
+    /// Cancels an originate started with [`EslConnection::bgapi_originate`].
+    ///
+    /// The leg is killed with `ORIGINATOR_CANCEL`. This races with the far end:
+    /// if the call is answered just before the kill arrives, the answered leg is
+    /// hung up and the background job still reports success. If the leg was
+    /// not created yet or is already gone, `ApiError` is returned.
+    pub async fn cancel_originate(&self, job_uuid: &str) -> Result<String, EslError> {
+        self.uuid_api(&format!("uuid_kill {} ORIGINATOR_CANCEL", job_uuid))
+            .await
+    }
+
+    /// Turns logging of the media packets of a channel on or off.
+    ///
+    /// At least one of `read` and `write` must be set.
+    pub async fn uuid_debug_media(
+        &self,
+        uuid: &str,
+        read: bool,
+        write: bool,
+        on: bool,
+    ) -> Result<String, EslError> {
+        let direction = match (read, write) {
+            (true, true) => "both",
+            (true, false) => "read",
+            (false, true) => "write",
+            (false, false) => {
+                return Err(EslError::InvalidArgument(
+                    "uuid_debug_media needs read or write".into(),
+                ))
+            }
+        };
+        let state = if on { "on" } else { "off" };
+        self.uuid_api(&format!(
+            "uuid_debug_media {} {} {}",
+            uuid, direction, state
+        ))
+        .await
+    }
+
+    /// Controls the file currently played on a channel
+    pub async fn uuid_fileman(&self, uuid: &str, cmd: FileManCommand) -> Result<String, EslError> {
+        self.uuid_api(&format!("uuid_fileman {} {}", uuid, cmd))
+            .await
+    }
+
+    /// Redirects a ringing or bridged call to `uri` with a SIP REFER.
+    ///
+    /// Returns the `api/response` event, or `ApiError` when freeswitch refuses,
+    /// e.g. because the channel isn't in a state which can be deflected.
+    pub async fn uuid_deflect(&self, uuid: &str, uri: &str) -> Result<Event, EslError> {
+        self.uuid_api_event(&format!("uuid_deflect {} {}", uuid, uri))
+            .await
+    }
+
+    /// Sends a SIP INFO with `body` of `content_type`, e.g. `application/dtmf-relay`.
+    ///
+    /// `uuid_send_info` takes the body on its command line, so it must not contain newlines.
+    pub async fn uuid_send_info(
+        &self,
+        uuid: &str,
+        content_type: &str,
+        body: &str,
+    ) -> Result<Event, EslError> {
+        let Some((mime_type, subtype)) = content_type.split_once('/') else {
+            return Err(EslError::InvalidArgument(format!(
+                "content type {:?} isn't type/subtype",
+                content_type
+            )));
+        };
+        if body.contains(['\r', '\n']) {
+            return Err(EslError::InvalidArgument(
+                "uuid_send_info body can't contain newlines".into(),
+            ));
+        }
+        self.uuid_api_event(&format!(
+            "uuid_send_info {} {} {} {}",
+            uuid, mime_type, subtype, body
+        ))
+        .await
+    }
+
+    /// Sends a chat message with `body` to the endpoint of the channel, e.g. a SIP MESSAGE.
+    ///
+    /// `uuid_chat` takes the text on its command line, so it must not contain newlines.
+    pub async fn uuid_chat(&self, uuid: &str, body: &str) -> Result<Event, EslError> {
+        if body.trim().is_empty() || body.contains(['\r', '\n']) {
+            return Err(EslError::InvalidArgument(format!(
+                "uuid_chat body {:?} must be a single non-empty line",
+                body
+            )));
+        }
+        self.uuid_api_event(&format!("uuid_chat {} {}", uuid, body))
+            .await
+    }
+
+    /// sends api command of the `uuid_*` family, returning the `api/response` event
+    async fn uuid_api_event(&self, command: &str) -> Result<Event, EslError> {
+        let event = Event::api_response(self.api_body(command).await?);
+        check_api_body(event.body().clone().unwrap_or_default(), UUID_API_FAILURES)?;
+        Ok(event)
+    }
+
+    /// Sets the level up to which freeswitch logs messages
+    pub async fn set_loglevel(&self, level: LogLevel) -> Result<String, EslError> {
+        self.api(&format!("fsctl loglevel {}", level)).await
+    }
+
+    /// Returns the level up to which freeswitch logs messages
+    pub async fn get_loglevel(&self) -> Result<LogLevel, EslError> {
+        // the reply looks like `+OK log level: DEBUG [7]`
+        let reply = self.api("fsctl loglevel").await?;
+        let level = reply
+            .rsplit_once('[')
+            .and_then(|(_, number)| number.split_once(']'))
+            .map(|(number, _)| number)
+            .or_else(|| reply.split_once("log level:").map(|(_, name)| name))
+            .unwrap_or(&reply);
+        level
+            .parse()
+            .map_err(|_| EslError::ApiError(reply.trim().to_string()))
+    }
+
+    /// Starts recording conference `conf` to `path`
+    pub async fn conference_record(&self, conf: &str, path: &str) -> Result<String, EslError> {
+        self.conference_api(&format!("conference {} record {}", conf, path))
+            .await
+    }
+
+    /// Stops the recording of conference `conf` to `path`, `all` stops every recording
+    pub async fn conference_stop_record(&self, conf: &str, path: &str) -> Result<String, EslError> {
+        self.conference_api(&format!("conference {} norecord {}", conf, path))
+            .await
+    }
+
+    /// Plays `file` to all members of conference `conf`, or only to `member`
+    pub async fn conference_play(
+        &self,
+        conf: &str,
+        file: &str,
+        member: Option<&str>,
+    ) -> Result<String, EslError> {
+        let mut command = format!("conference {} play {}", conf, file);
+        if let Some(member) = member {
+            command.push_str(&format!(" {}", member));
+        }
+        self.conference_api(&command).await
+    }
+
+    /// sends `conference` api command, which reports a missing conference without `-ERR`
+    async fn conference_api(&self, command: &str) -> Result<String, EslError> {
+        let body = self.api(command).await?;
+        let text = body.trim();
+        if text.starts_with("Conference ") && text.ends_with("not found") {
+            return Err(EslError::ApiError(text.to_string()));
+        }
+        Ok(body)
+    }
+
+    /// Sends a talk or hold event to the phone of a channel
+    pub async fn uuid_phone_event(
+        &self,
+        uuid: &str,
+        event: PhoneEvent,
+    ) -> Result<String, EslError> {
+        self.uuid_api(&format!("uuid_phone_event {} {}", uuid, event))
+            .await
+    }
+
+    /// Signals ringing to the caller of a channel which isn't answered yet
+    pub async fn uuid_ring_ready(&self, uuid: &str) -> Result<String, EslError> {
+        self.uuid_api(&format!("uuid_ring_ready {}", uuid)).await
+    }
+
+    /// Starts early media on a channel without answering it, e.g. for custom ring-back
+    pub async fn uuid_pre_answer(&self, uuid: &str) -> Result<String, EslError> {
+        self.uuid_api(&format!("uuid_pre_answer {}", uuid)).await
+    }
+
+    /// Adjusts the jitter buffer of a live channel.
+    ///
+    /// `spec` is `off` or `<length>[:<max length>[:<max drift>]]` in milliseconds.
+    pub async fn uuid_jitterbuffer(&self, uuid: &str, spec: &str) -> Result<String, EslError> {
+        let parts: Vec<&str> = spec.split(':').collect();
+        let valid = spec == "off"
+            || (parts.len() <= 3 && parts.iter().all(|part| part.parse::<u32>().is_ok()));
+        if !valid {
+            return Err(EslError::InvalidArgument(format!(
+                "invalid jitterbuffer spec {:?}",
+                spec
+            )));
+        }
+        self.uuid_api(&format!("uuid_jitterbuffer {} {}", uuid, spec))
+            .await
+    }
+
+    /// Stores the current media stats of a live channel in its `rtp_audio_*` variables
+    pub async fn uuid_set_media_stats(&self, uuid: &str) -> Result<String, EslError> {
+        self.uuid_api(&format!("uuid_set_media_stats {}", uuid))
+            .await
+    }
+
+    /// Returns values of the channel variables `names` fetched with one `uuid_dump`.
+    ///
+    /// Variables which aren't set map to `None`.
+    pub async fn uuid_getvars(
+        &self,
+        uuid: &str,
+        names: &[&str],
+    ) -> Result<HashMap<String, Option<String>>, EslError> {
+        let dump = self.uuid_api(&format!("uuid_dump {} json", uuid)).await?;
+        let fields: HashMap<String, Value> = serde_json::from_str(&dump)?;
+        Ok(names
+            .iter()
+            .map(|name| {
+                let value = field_string(&fields, &format!("variable_{}", name));
+                (name.to_string(), value)
+            })
+            .collect())
+    }
+
+    /// Returns paths of the active recordings of a channel.
+    ///
+    /// `uuid_record` has no status subcommand, so the `session_record` media
+    /// bugs listed by `uuid_buglist` are used instead.
+    pub async fn recordings(&self, uuid: &str) -> Result<Vec<String>, EslError> {
+        let buglist = self.uuid_api(&format!("uuid_buglist {}", uuid)).await?;
+        Ok(parse_recordings(&buglist))
+    }
+
+    /// Expands the channel and global variables of `expression` on the server,
+    /// e.g. `${sofia_contact(1000@${domain})}` to build a dialstring.
+    ///
+    /// The result is returned as is, without parsing a `+OK` prefix.
+    pub async fn eval(&self, expression: &str) -> Result<String, EslError> {
+        let body = self.api_bytes(&format!("eval {}", expression)).await?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+}
+
+/// Derives a stable uuid from `key` with 128 bit FNV-1a, so retries from
+/// another process or version of this crate map to the same channel
+fn idempotency_uuid(key: &str) -> String {
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    let hash = key.bytes().fold(OFFSET, |hash, byte| {
+        (hash ^ byte as u128).wrapping_mul(PRIME)
+    });
+    uuid::Uuid::from_u128(hash).to_string()
+}
+
+fn xml_tag<'a>(src: &'a str, tag: &str) -> Option<&'a str> {
+    let start = src.find(&format!("<{}>", tag))? + tag.len() + 2;
+    let end = src[start..].find(&format!("</{}>", tag))? + start;
+    Some(src[start..end].trim())
+}
+
+/// Prepends `variables` and `origination_uuid` to the channel variables of originate `args`
+pub(crate) fn with_origination_uuid(args: &str, variables: &str, uuid: &str) -> String {
+    match args.strip_prefix('{') {
+        Some(rest) => format!("{{{}origination_uuid={},{}", variables, uuid, rest),
+        None => format!("{{{}origination_uuid={}}}{}", variables, uuid, args),
+    }
+}
+
+fn parse_recordings(buglist: &str) -> Vec<String> {
+    buglist
+        .split("<media-bug>")
+        .skip(1)
+        .filter(|bug| xml_tag(bug, "function") == Some("session_record"))
+        .filter_map(|bug| xml_tag(bug, "target"))
+        .map(|target| target.to_string())
+        .collect()
+}
@@ -0,0 +1,131 @@
+use crate::{EslConnection, EslError, Event, PlaybackBuilder};
+
+/// Typed arguments of a dialplan application, for [`EslConnection::execute_typed`]
+pub trait ToAppArgs {
+    /// Name of the application
+    fn app_name(&self) -> &str;
+    /// Arguments formatted as freeswitch expects them
+    fn to_app_args(&self) -> String;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Arguments of `playback`
+pub struct PlaybackArgs {
+    file: String,
+}
+
+impl PlaybackArgs {
+    /// Creates arguments playing `file`
+    pub fn new(file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+        }
+    }
+}
+
+impl ToAppArgs for PlaybackArgs {
+    fn app_name(&self) -> &str {
+        "playback"
+    }
+    fn to_app_args(&self) -> String {
+        self.file.clone()
+    }
+}
+
+impl ToAppArgs for PlaybackBuilder {
+    fn app_name(&self) -> &str {
+        "playback"
+    }
+    fn to_app_args(&self) -> String {
+        self.build()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Arguments of `bridge` to one or more endpoints
+pub struct BridgeArgs {
+    endpoints: Vec<String>,
+    variables: Vec<(String, String)>,
+    sequential: bool,
+}
+
+impl BridgeArgs {
+    /// Creates arguments bridging to `endpoint`, e.g. `user/1000`
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoints: vec![endpoint.to_string()],
+            variables: Vec::new(),
+            sequential: false,
+        }
+    }
+
+    /// Adds another endpoint, rung at the same time unless [`BridgeArgs::sequential`]
+    pub fn endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoints.push(endpoint.to_string());
+        self
+    }
+
+    /// Rings the endpoints one after another instead of at the same time
+    pub fn sequential(mut self, sequential: bool) -> Self {
+        self.sequential = sequential;
+        self
+    }
+
+    /// Sets a channel variable on all legs, e.g. `call_timeout`
+    pub fn variable(mut self, name: &str, value: &str) -> Self {
+        self.variables.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Quotes values which would otherwise end a `{name=value}` variable list
+pub(crate) fn quote_variable(value: &str) -> String {
+    if value.contains([',', ' ', '}']) {
+        format!("'{}'", value.replace('\'', "\\'"))
+    } else {
+        value.to_string()
+    }
+}
+
+impl ToAppArgs for BridgeArgs {
+    fn app_name(&self) -> &str {
+        "bridge"
+    }
+    fn to_app_args(&self) -> String {
+        let mut args = String::new();
+        if !self.variables.is_empty() {
+            let variables: Vec<String> = self
+                .variables
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, quote_variable(value)))
+                .collect();
+            args.push_str(&format!("{{{}}}", variables.join(",")));
+        }
+        let separator = if self.sequential { "|" } else { "," };
+        args.push_str(&self.endpoints.join(separator));
+        args
+    }
+}
+
+impl EslConnection {
+    /// Executes the application of typed arguments, see [`EslConnection::execute`]
+    pub async fn execute_typed<A: ToAppArgs>(&self, args: A) -> Result<Event, EslError> {
+        self.execute(args.app_name(), &args.to_app_args()).await
+    }
+
+    /// Bridges the call to `call_url`, setting `vars` on the new leg as `{name=value,...}`.
+    ///
+    /// Values containing commas, spaces or braces are quoted like in [`BridgeArgs::variable`].
+    pub async fn bridge_to(
+        &self,
+        call_url: &str,
+        vars: &[(&str, &str)],
+    ) -> Result<Event, EslError> {
+        let args = vars
+            .iter()
+            .fold(BridgeArgs::new(call_url), |args, (name, value)| {
+                args.variable(name, value)
+            });
+        self.execute_typed(args).await
+    }
+}
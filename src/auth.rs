@@ -0,0 +1,36 @@
+use std::fmt;
+
+use crate::Event;
+
+/// Answer of an inbound connection to the `auth/request` freeswitch sends first.
+///
+/// The reply to the command is checked like one of `auth`, i.e. `+OK` accepts the
+/// connection and `-ERR invalid` fails it with `AuthFailed`.
+pub trait AuthMethod: fmt::Debug + Send + Sync {
+    /// Returns the command answering `request`, or `None` when the socket is
+    /// accepted without one, e.g. when freeswitch admits the address by its ACL
+    fn auth_command(&self, request: &Event) -> Option<String>;
+}
+
+#[derive(Clone, PartialEq, Eq)]
+/// Plaintext `auth <password>`, the default of [`crate::Esl::inbound`]
+pub struct PasswordAuth(String);
+
+impl PasswordAuth {
+    /// Creates auth with `password`
+    pub fn new(password: impl ToString) -> Self {
+        Self(password.to_string())
+    }
+}
+
+impl fmt::Debug for PasswordAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PasswordAuth(..)")
+    }
+}
+
+impl AuthMethod for PasswordAuth {
+    fn auth_command(&self, _request: &Event) -> Option<String> {
+        Some(format!("auth {}", self.0))
+    }
+}
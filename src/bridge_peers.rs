@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+use crate::event::field_string;
+use crate::{BridgeEvent, EslConnection};
+
+#[derive(Debug, Clone, Default)]
+/// Other leg of each channel from its last `CHANNEL_BRIDGE`, filled by the reader task
+pub(crate) struct BridgePeers(Arc<Mutex<HashMap<String, String>>>);
+
+impl BridgePeers {
+    /// Stores both legs of a bridge event and forgets destroyed channels, ignoring other events
+    pub(crate) fn record(&self, fields: &HashMap<String, Value>) {
+        match fields.get("Event-Name").and_then(Value::as_str) {
+            Some("CHANNEL_BRIDGE") => {
+                let bridge = BridgeEvent::from_fields(fields);
+                if let (Some(a_leg), Some(b_leg)) = (bridge.a_leg_uuid, bridge.b_leg_uuid) {
+                    let mut peers = self.0.lock().unwrap();
+                    peers.insert(a_leg.clone(), b_leg.clone());
+                    peers.insert(b_leg, a_leg);
+                }
+            }
+            Some("CHANNEL_DESTROY") => {
+                if let Some(uuid) = field_string(fields, "Unique-ID") {
+                    self.0.lock().unwrap().remove(&uuid);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn get(&self, uuid: &str) -> Option<String> {
+        self.0.lock().unwrap().get(uuid).cloned()
+    }
+}
+
+impl EslConnection {
+    /// Returns the leg `uuid` was bridged to by its last `CHANNEL_BRIDGE` event.
+    ///
+    /// The peer is kept after `CHANNEL_UNBRIDGE` until the channel is destroyed.
+    /// Inbound connections only see the events after subscribing to them.
+    pub fn bridged_peer(&self, uuid: &str) -> Option<String> {
+        self.bridge_peers.get(uuid)
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use serde_json::Value;
+
+use crate::event::field_string;
+use crate::{EslConnection, EslError, Event};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Times of a call from the `Caller-Channel-*-Time` headers of a channel.
+///
+/// Freeswitch reports `0` for steps which did not happen yet, like the answer of an
+/// unanswered call or the hangup of a live one; those and missing headers are `None`.
+pub struct CallTimestamps {
+    /// When the channel was created
+    pub created: Option<SystemTime>,
+    /// When the channel was answered
+    pub answered: Option<SystemTime>,
+    /// When the channel was hung up
+    pub hangup: Option<SystemTime>,
+}
+
+fn timestamp(fields: &HashMap<String, Value>, name: &str) -> Option<SystemTime> {
+    let micros = field_string(fields, &format!("Caller-Channel-{}-Time", name))?
+        .parse::<u64>()
+        .ok()
+        .filter(|micros| *micros > 0)?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_micros(micros))
+}
+
+impl From<&HashMap<String, Value>> for CallTimestamps {
+    fn from(fields: &HashMap<String, Value>) -> Self {
+        Self {
+            created: timestamp(fields, "Created"),
+            answered: timestamp(fields, "Answered"),
+            hangup: timestamp(fields, "Hangup"),
+        }
+    }
+}
+
+impl From<&Event> for CallTimestamps {
+    fn from(event: &Event) -> Self {
+        Self::from(&event.fields())
+    }
+}
+
+impl EslConnection {
+    /// Returns created, answered and hangup times of a channel read with `uuid_dump`
+    pub async fn call_timestamps(&self, uuid: &str) -> Result<CallTimestamps, EslError> {
+        let dump = self.uuid_api(&format!("uuid_dump {} json", uuid)).await?;
+        let fields: HashMap<String, Value> = serde_json::from_str(&dump)?;
+        Ok(CallTimestamps::from(&fields))
+    }
+}
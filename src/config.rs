@@ -0,0 +1,238 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::{mpsc::unbounded_channel, Mutex};
+use tracing::warn;
+
+use crate::io::EslCodec;
+use crate::metrics::{Metrics, NoMetrics};
+
+type TraceWriter = Arc<Mutex<Box<dyn AsyncWrite + Send + Unpin>>>;
+type DisconnectHook = Arc<std::sync::Mutex<Box<dyn Fn() + Send>>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+/// Handling of frames with an unknown `Content-Type`, e.g. `log/data`
+pub enum UnknownFramePolicy {
+    /// Drops the frame with a warning
+    #[default]
+    Ignore,
+    /// Sends the frame to [`crate::EslConnection::events`]
+    RouteToEvents,
+    /// Closes the connection as freeswitch broke the protocol, failing the waiting
+    /// and later commands with `Disconnected`.
+    ///
+    /// The frame isn't a reply, so no single command can be failed with it
+    /// without passing the replies of later commands to the wrong caller.
+    Error,
+}
+
+#[derive(Clone)]
+/// Options for [`crate::Esl::inbound_with_config`] and [`crate::Esl::outbound_with_config`]
+pub struct EslConfig {
+    trace_io: Option<TraceWriter>,
+    pub(crate) auth_retries: u32,
+    pub(crate) auth_retry_delay: Duration,
+    pub(crate) tcp_nodelay: bool,
+    pub(crate) read_buffer_capacity: usize,
+    pub(crate) api_timeout: Option<Duration>,
+    pub(crate) bgapi_timeout: Option<Duration>,
+    pub(crate) command_timeout: Option<Duration>,
+    pub(crate) strict_events: bool,
+    pub(crate) metrics: Arc<dyn Metrics>,
+    pub(crate) lenient_setup: bool,
+    on_disconnect: Option<DisconnectHook>,
+    pub(crate) unknown_frame_policy: UnknownFramePolicy,
+    retain_raw_frames: bool,
+    pub(crate) heartbeat_interval: Option<Duration>,
+    pub(crate) dtmf_buffer: usize,
+}
+
+impl Default for EslConfig {
+    fn default() -> Self {
+        Self {
+            trace_io: None,
+            auth_retries: 0,
+            auth_retry_delay: Duration::from_millis(200),
+            tcp_nodelay: true,
+            read_buffer_capacity: 8 * 1024,
+            api_timeout: None,
+            bgapi_timeout: None,
+            command_timeout: None,
+            strict_events: false,
+            metrics: Arc::new(NoMetrics),
+            lenient_setup: false,
+            on_disconnect: None,
+            unknown_frame_policy: UnknownFramePolicy::default(),
+            retain_raw_frames: false,
+            heartbeat_interval: None,
+            dtmf_buffer: 0,
+        }
+    }
+}
+
+impl fmt::Debug for EslConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EslConfig")
+            .field("trace_io", &self.trace_io.is_some())
+            .field("auth_retries", &self.auth_retries)
+            .field("auth_retry_delay", &self.auth_retry_delay)
+            .field("tcp_nodelay", &self.tcp_nodelay)
+            .field("read_buffer_capacity", &self.read_buffer_capacity)
+            .field("api_timeout", &self.api_timeout)
+            .field("bgapi_timeout", &self.bgapi_timeout)
+            .field("command_timeout", &self.command_timeout)
+            .field("strict_events", &self.strict_events)
+            .field("lenient_setup", &self.lenient_setup)
+            .field("on_disconnect", &self.on_disconnect.is_some())
+            .field("unknown_frame_policy", &self.unknown_frame_policy)
+            .field("retain_raw_frames", &self.retain_raw_frames)
+            .field("heartbeat_interval", &self.heartbeat_interval)
+            .field("dtmf_buffer", &self.dtmf_buffer)
+            .finish()
+    }
+}
+
+impl EslConfig {
+    /// Creates config with default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Copies all raw bytes sent and received to `writer`, for debugging the protocol
+    pub fn trace_io(mut self, writer: impl AsyncWrite + Send + Unpin + 'static) -> Self {
+        self.trace_io = Some(Arc::new(Mutex::new(Box::new(writer))));
+        self
+    }
+
+    /// Retries auth up to `retries` times when freeswitch rejects it for
+    /// another reason than a wrong password, waiting `delay` in between
+    pub fn auth_retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.auth_retries = retries;
+        self.auth_retry_delay = delay;
+        self
+    }
+
+    /// Sets `TCP_NODELAY` on the stream so small commands aren't delayed, defaults to true
+    pub fn tcp_nodelay(mut self, nodelay: bool) -> Self {
+        self.tcp_nodelay = nodelay;
+        self
+    }
+
+    /// Sets initial capacity of the read buffer in bytes, defaults to 8 KiB
+    pub fn read_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.read_buffer_capacity = capacity;
+        self
+    }
+
+    /// Sets time to wait for the reply of an `api` command, unlimited by default
+    pub fn api_timeout(mut self, timeout: Duration) -> Self {
+        self.api_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets time to wait for the `BACKGROUND_JOB` event of a `bgapi` command, unlimited by default
+    pub fn bgapi_timeout(mut self, timeout: Duration) -> Self {
+        self.bgapi_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets time to wait for the reply of other commands, unlimited by default
+    pub fn command_timeout(mut self, timeout: Duration) -> Self {
+        self.command_timeout = Some(timeout);
+        self
+    }
+
+    /// Makes `subscribe` return `UnknownEvent` for names freeswitch doesn't know,
+    /// instead of freeswitch silently ignoring them
+    pub fn strict_events(mut self, strict: bool) -> Self {
+        self.strict_events = strict;
+        self
+    }
+
+    /// Reports command and connection stats to `metrics`
+    pub fn metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+        self.metrics = Arc::new(metrics);
+        self
+    }
+
+    /// Keeps an outbound connection when its `subscribe` or `myevents` fails,
+    /// e.g. because the channel already hung up, logging a warning instead.
+    ///
+    /// This lets the handler still clean up, but the connection may not receive
+    /// events: an `execute` then waits for a completion which never arrives,
+    /// so the cleanup should use timeouts or api commands.
+    pub fn lenient_setup(mut self, lenient: bool) -> Self {
+        self.lenient_setup = lenient;
+        self
+    }
+
+    /// Calls `callback` once when freeswitch disconnects or closes the socket
+    pub fn on_disconnect(mut self, callback: impl Fn() + Send + 'static) -> Self {
+        self.on_disconnect = Some(Arc::new(std::sync::Mutex::new(Box::new(callback))));
+        self
+    }
+
+    /// Sets what happens with frames of an unknown `Content-Type`, ignored by default
+    pub fn unknown_frame_policy(mut self, policy: UnknownFramePolicy) -> Self {
+        self.unknown_frame_policy = policy;
+        self
+    }
+
+    /// Keeps the bytes of every received frame, needed by [`crate::EslConnection::send_recv_raw`]
+    pub fn retain_raw_frames(mut self, retain: bool) -> Self {
+        self.retain_raw_frames = retain;
+        self
+    }
+
+    /// Sets interval of `HEARTBEAT` events, 20 seconds unless changed in freeswitch.
+    ///
+    /// While they are subscribed, [`crate::EslConnection::connected`] turns false when
+    /// none arrives within twice the interval, e.g. on a silently dropped TCP connection.
+    pub fn heartbeat_interval(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = Some(interval);
+        self
+    }
+
+    /// Keeps the last `max_digits` digits of `DTMF` events for
+    /// [`crate::EslConnection::drain_dtmf`], disabled by default.
+    ///
+    /// Digits of all channels whose events the connection receives are mixed,
+    /// so on an inbound connection this is only useful together with `myevents`.
+    pub fn dtmf_buffer(mut self, max_digits: usize) -> Self {
+        self.dtmf_buffer = max_digits;
+        self
+    }
+
+    /// Returns callback to run when the reader task of a connection stops
+    pub(crate) fn disconnect_hook(&self) -> impl FnOnce() {
+        let hook = self.on_disconnect.clone();
+        move || {
+            if let Some(hook) = hook {
+                let callback = hook.lock().unwrap_or_else(|e| e.into_inner());
+                callback();
+            }
+        }
+    }
+
+    /// Creates codec for a new connection, starting the trace writer when enabled
+    pub(crate) fn codec(&self) -> EslCodec {
+        let Some(writer) = self.trace_io.clone() else {
+            return EslCodec::new(None, self.retain_raw_frames);
+        };
+        let (tap, mut tapped) = unbounded_channel::<Bytes>();
+        tokio::spawn(async move {
+            while let Some(bytes) = tapped.recv().await {
+                let mut writer = writer.lock().await;
+                if let Err(e) = writer.write_all(&bytes).await {
+                    warn!("Unable to write esl trace: {}", e);
+                    return;
+                }
+                let _ = writer.flush().await;
+            }
+        });
+        EslCodec::new(Some(tap), self.retain_raw_frames)
+    }
+}
@@ -1,23 +1,48 @@
+use crate::auth::AuthMethod;
+use crate::bridge_peers::BridgePeers;
 use crate::code::{Code, ParseCode};
+use crate::config::{EslConfig, UnknownFramePolicy};
+use crate::connection_info::ConnectionInfo;
+use crate::dtmf::DtmfBuffer;
+use crate::heartbeat::HeartbeatWatch;
+use crate::hold::HoldState;
+use crate::stats::{ConnectionStats, StatsCounters};
+use crate::subscription::Subscriptions;
 use crate::error::EslError;
 use crate::esl::EslConnectionType;
-use crate::event::Event;
-use crate::io::EslCodec;
+use crate::event::{trim_body, Event};
+use crate::event_types::{subscription_names, unknown_event, EventSubscription};
+use crate::io::{EslCodec, InboundResponse};
+use crate::metrics::Metrics;
+use bytes::Bytes;
 use futures::SinkExt;
 use serde::de::DeserializeOwned;
 use serde_json::Value;
 use std::collections::{HashMap, VecDeque};
 use std::sync::atomic::Ordering;
 use std::sync::{atomic::AtomicBool, Arc};
+use std::time::Duration;
 use tokio::io::{WriteHalf, ReadHalf};
 use tokio::net::TcpStream;
 use tokio::sync::{
-    oneshot::{channel, Sender},
+    broadcast::{
+        self,
+        error::{RecvError, TryRecvError},
+        WeakSender,
+    },
+    oneshot::{channel, Receiver, Sender},
     Mutex,
 };
-use tokio_stream::StreamExt;
+use tokio_stream::{Stream, StreamExt};
 use tokio_util::codec::{FramedRead, FramedWrite};
-use tracing::trace;
+use tokio_util::sync::DropGuard;
+use tracing::{trace, warn};
+
+/// Number of events buffered for each event stream before old ones are dropped
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Longest application argument sent as `execute-app-arg` header, longer ones go in the body
+const MAX_APP_ARG_HEADER_LENGTH: usize = 1024;
 
 #[derive(Debug)]
 ///Simple ESL connection that doesn't do asynchronous notifications
@@ -31,7 +56,8 @@ impl EslConnectionSimple{
         stream: TcpStream,
         password: &str,
     ) -> Result<Self, EslError> {
-        let esl_codec = EslCodec {};
+        stream.set_nodelay(true)?;
+        let esl_codec = EslCodec::default();
         let (read_half, write_half) = tokio::io::split(stream);
         let rx = FramedRead::new(read_half, esl_codec.clone());
         let tx = FramedWrite::new(write_half, esl_codec.clone());
@@ -44,7 +70,7 @@ impl EslConnectionSimple{
     }
     async fn auth(&mut self, password: &str) -> Result<(), EslError>{
         //Remote sends first with either an auth request or an ACL rejection
-        let event = self.rx.next().await.ok_or_else(||EslError::InternalError("Didn't get auth request message".into()))??;
+        let event = self.rx.next().await.ok_or_else(||EslError::InternalError("Didn't get auth request message".into()))??.into_event();
         if let Some(event_type) = event.headers.get("Content-Type") {
             match event_type.as_str().unwrap() {
                 "auth/request" => trace!("Got auth request. Continuing."),
@@ -54,7 +80,7 @@ impl EslConnectionSimple{
         }
         self.tx.send(format!("auth {password}").as_bytes()).await?;
 
-        let reply = self.rx.next().await.ok_or_else(||EslError::InternalError("Didn't get auth reply message".into()))??;
+        let reply = self.rx.next().await.ok_or_else(||EslError::InternalError("Didn't get auth reply message".into()))??.into_event();
         if let Some(event_type) = reply.headers.get("Content-Type") {
             match event_type.as_str().unwrap() {
                 "command/reply" => {
@@ -77,14 +103,15 @@ impl EslConnectionSimple{
     ///Sends a request and waits for the reply
     pub async fn send_recv(&mut self, data: &[u8]) -> Result<Event, EslError>{
         self.tx.send(data).await?;
-        self.rx.next().await.unwrap()
+        self.rx.next().await.unwrap().map(InboundResponse::into_event)
     }
     /// sends api command to freeswitch. Will return the result of the API call
     pub async fn api_raw(&mut self, command: &str) -> Result<String, EslError> {
         let response = self.send_recv(format!("api {}", command).as_bytes()).await;
         let event = response?;
         let body = event
-            .body
+            .body()
+            .clone()
             .ok_or_else(|| EslError::InternalError("Didnt get body in api response".into()))?;
         Ok(body)
     }
@@ -102,25 +129,48 @@ impl EslConnectionSimple{
 #[derive(Debug)]
 /// contains Esl connection with freeswitch
 pub struct EslConnection {
-    password: String,
-    commands: Arc<Mutex<VecDeque<Sender<Event>>>>,
+    auth: Option<Arc<dyn AuthMethod>>,
+    commands: Arc<Mutex<VecDeque<Sender<InboundResponse>>>>,
     transport_tx: Arc<Mutex<FramedWrite<WriteHalf<TcpStream>, EslCodec>>>,
     background_jobs: Arc<Mutex<HashMap<String, Sender<Event>>>>,
-    connected: AtomicBool,
+    connected: Arc<AtomicBool>,
+    closed: AtomicBool,
+    /// set by the reader task when it stops, under the lock of `commands`
+    reader_stopped: Arc<AtomicBool>,
     pub(crate) call_uuid: Option<String>,
-    connection_info: Option<HashMap<String, Value>>,
+    connection_info: Option<ConnectionInfo>,
+    subscriptions: std::sync::Mutex<Subscriptions>,
+    pub(crate) filters: std::sync::Mutex<Vec<(String, String)>>,
+    pub(crate) myevents_uuid: std::sync::Mutex<Option<String>>,
+    pub(crate) playback_terminators: std::sync::Mutex<Option<String>>,
+    events: WeakSender<Event>,
+    /// events received during setup, before any stream could listen
+    early_events: std::sync::Mutex<Vec<Event>>,
+    pub(crate) dtmf: DtmfBuffer,
+    pub(crate) holds: HoldState,
+    pub(crate) bridge_peers: BridgePeers,
+    heartbeats: HeartbeatWatch,
+    /// stops the heartbeat watchdog when the connection is dropped
+    watchdog: Option<DropGuard>,
+    stats: StatsCounters,
+    config: EslConfig,
 }
 
 impl EslConnection {
     /// Returns one of the session parameters as a string
     pub fn get_info_string(&self, key: &str) -> Option<String> {
-        let value = self.connection_info.as_ref()?.get(key)?.clone();
+        let value = self.connection_info.as_ref()?.raw().get(key)?.clone();
         serde_json::from_value(value).ok()?
     }
 
+    /// Returns channel data of the call in outbound mode
+    pub fn connection_info(&self) -> Option<&ConnectionInfo> {
+        self.connection_info.as_ref()
+    }
+
     /// Returns one of the session parameters as any deserializable type
     pub fn get_info<V: DeserializeOwned>(&self, key: &str) -> Option<V> {
-        let value = self.connection_info.as_ref()?.get(key)?.clone();
+        let value = self.connection_info.as_ref()?.raw().get(key)?.clone();
         serde_json::from_value(value).ok()?
     }
 
@@ -130,42 +180,293 @@ impl EslConnection {
     }
     /// disconnects from freeswitch
     pub async fn disconnect(self) -> Result<(), EslError> {
-        self.send_recv(b"exit").await?;
+        self.close().await
+    }
+    /// Disconnects from freeswitch without consuming the connection, so all
+    /// holders of a shared handle see it closed.
+    ///
+    /// Later commands return [`EslError::NotConnected`].
+    pub async fn close(&self) -> Result<(), EslError> {
+        let reply = self.send_recv(b"exit").await;
+        self.closed.store(true, Ordering::Relaxed);
         self.connected.store(false, Ordering::Relaxed);
-        Ok(())
+        reply.map(|_| ())
+    }
+    /// Returns stream of events which are not the result of a command of this connection.
+    ///
+    /// Only events arriving after this call are yielded, and only for the event
+    /// types subscribed with [`EslConnection::subscribe`]. The stream ends when
+    /// the connection is closed. Events freeswitch sent during setup are kept
+    /// apart and returned by [`EslConnection::take_early_events`].
+    pub fn events(&self) -> impl Stream<Item = Event> {
+        let rx = self.events.upgrade().map(|events| events.subscribe());
+        futures::stream::unfold(rx, |rx| async move {
+            let mut rx = rx?;
+            loop {
+                match rx.recv().await {
+                    Ok(event) => return Some((event, Some(rx))),
+                    Err(RecvError::Lagged(skipped)) => {
+                        warn!("event stream lagged, dropped {} events", skipped)
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Takes the events freeswitch sent during setup, e.g. before the `connect`
+    /// reply of an outbound connection.
+    ///
+    /// Later calls return an empty list.
+    pub fn take_early_events(&self) -> Vec<Event> {
+        std::mem::take(&mut *self.early_events.lock().unwrap())
+    }
+
+    /// Returns stream of [`EslConnection::events`] whose `Event-Name` is one of `names`
+    pub fn events_filtered(&self, names: &[&str]) -> impl Stream<Item = Event> {
+        let names: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+        self.events().filter(move |event| {
+            event
+                .event_name()
+                .is_some_and(|name| names.contains(&name))
+        })
+    }
+
+    /// Returns counters of this connection, to notice one which stopped responding
+    pub async fn stats(&self) -> ConnectionStats {
+        let pending_commands = self.commands.lock().await.len();
+        let pending_jobs = self.background_jobs.lock().await.len();
+        self.stats.snapshot(pending_commands, pending_jobs)
     }
+
     /// returns status of esl connection
     pub fn connected(&self) -> bool {
         self.connected.load(Ordering::Relaxed)
     }
-    pub(crate) async fn send(&self, item: &[u8]) -> Result<(), EslError> {
-        let mut transport = self.transport_tx.lock().await;
-        transport.send(item).await
+    /// Fails with `NotConnected` after [`EslConnection::close`] and with `Disconnected`
+    /// once the reader stopped, as no reply would reach a command sent then.
+    ///
+    /// Checked again while `commands` is locked, so no slot is queued after the
+    /// reader dropped the waiting ones.
+    fn ensure_open(&self) -> Result<(), EslError> {
+        if self.closed.load(Ordering::Relaxed) {
+            return Err(EslError::NotConnected);
+        }
+        if self.reader_stopped.load(Ordering::Relaxed) {
+            return Err(EslError::Disconnected);
+        }
+        Ok(())
     }
     /// sends raw message to freeswitch and receives reply
     pub async fn send_recv(&self, item: &[u8]) -> Result<Event, EslError> {
-        self.send(item).await?;
+        let reply = self
+            .send_recv_within(item, self.config.command_timeout)
+            .await?;
+        Ok(reply.into_event())
+    }
+
+    /// sends raw message to freeswitch and returns the reply frame exactly as received.
+    ///
+    /// Needs [`EslConfig::retain_raw_frames`], otherwise the bytes are not kept.
+    pub async fn send_recv_raw(&self, item: &[u8]) -> Result<Vec<u8>, EslError> {
+        let reply = self.send_recv(item).await?;
+        reply.raw_frame.map(|frame| frame.to_vec()).ok_or_else(|| {
+            EslError::InvalidArgument("raw frames are not retained by this connection".into())
+        })
+    }
+
+    /// sends raw message and waits at most `timeout` for the reply
+    async fn send_recv_within(
+        &self,
+        item: &[u8],
+        timeout: Option<Duration>,
+    ) -> Result<InboundResponse, EslError> {
+        self.ensure_open()?;
         let (tx, rx) = channel();
-        self.commands.lock().await.push_back(tx);
-        Ok(rx.await?)
+        let in_flight = {
+            // queued under the transport lock, so slots are in the order of the writes
+            let mut transport = self.transport_tx.lock().await;
+            let in_flight = {
+                let mut commands = self.commands.lock().await;
+                self.ensure_open()?;
+                commands.push_back(tx);
+                commands.len()
+            };
+            if let Err(e) = transport.send(item).await {
+                // no other command is queued while the transport is locked
+                self.commands.lock().await.pop_back();
+                return Err(e);
+            }
+            in_flight
+        };
+        self.config.metrics.command_sent();
+        self.config.metrics.in_flight(in_flight);
+        self.stats.command_sent();
+        within(timeout, item, rx).await??.expected()
+    }
+
+    /// sends raw messages with a single flush and receives their replies.
+    ///
+    /// The messages are written in order without other commands of this
+    /// connection in between, and the replies are returned in the same order.
+    pub async fn send_batch(&self, items: &[&[u8]]) -> Result<Vec<Event>, EslError> {
+        let receivers = self.feed_batch(items).await?;
+        let mut replies = Vec::with_capacity(receivers.len());
+        for rx in receivers {
+            replies.push(rx.await?.expected()?.into_event());
+        }
+        Ok(replies)
+    }
+
+    /// sends raw messages with a single flush and receives only the reply of the last.
+    ///
+    /// Freeswitch replies in order, so the earlier replies arrive first and are
+    /// discarded as they come in.
+    pub async fn send_recv_last(&self, items: &[&[u8]]) -> Result<Event, EslError> {
+        let mut receivers = self.feed_batch(items).await?;
+        let last = receivers
+            .pop()
+            .ok_or_else(|| EslError::InvalidArgument("no messages to send".into()))?;
+        drop(receivers);
+        Ok(last.await?.expected()?.into_event())
+    }
+
+    /// writes messages in order and returns receivers of their replies
+    async fn feed_batch(
+        &self,
+        items: &[&[u8]],
+    ) -> Result<Vec<Receiver<InboundResponse>>, EslError> {
+        self.ensure_open()?;
+        let mut receivers = Vec::with_capacity(items.len());
+        {
+            let mut transport = self.transport_tx.lock().await;
+            let mut commands = self.commands.lock().await;
+            self.ensure_open()?;
+            let mut written = async {
+                for item in items {
+                    transport.feed(*item).await?;
+                    let (tx, rx) = channel();
+                    commands.push_back(tx);
+                    receivers.push(rx);
+                }
+                Ok::<_, EslError>(())
+            }
+            .await;
+            let in_flight = commands.len();
+            drop(commands);
+            if written.is_ok() {
+                written = transport.flush().await;
+            }
+            if let Err(e) = written {
+                // the batch's slots are last, as no other command is queued while the
+                // transport is locked, less those the reader already took from the front
+                let mut commands = self.commands.lock().await;
+                let queued = commands.len();
+                commands.truncate(queued.saturating_sub(receivers.len()));
+                return Err(e);
+            }
+            for _ in items {
+                self.config.metrics.command_sent();
+                self.stats.command_sent();
+            }
+            self.config.metrics.in_flight(in_flight);
+        }
+        Ok(receivers)
+    }
+
+    /// stops waiting for the background job or application `uuid`, for synchronous
+    /// callers like `Drop`
+    pub(crate) fn forget_job(&self, uuid: &str) {
+        if let Ok(mut jobs) = self.background_jobs.try_lock() {
+            jobs.remove(uuid);
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let jobs = Arc::clone(&self.background_jobs);
+        let uuid = uuid.to_string();
+        runtime.spawn(async move {
+            jobs.lock().await.remove(&uuid);
+        });
+    }
+
+    /// Returns a guard forgetting the job `uuid` when dropped, so a wait which is
+    /// given up before the completion arrived leaves no entry behind
+    pub(crate) fn job_guard(&self, uuid: String) -> JobGuard<'_> {
+        JobGuard {
+            connection: self,
+            uuid,
+        }
+    }
+
+    /// sends raw message without waiting, for synchronous callers like `Drop`.
+    ///
+    /// The reply is discarded, its slot is queued together with the write so
+    /// replies of other commands stay in order.
+    pub(crate) fn send_detached(&self, item: Vec<u8>) {
+        if self.ensure_open().is_err() {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            warn!("no runtime to send {:?}", String::from_utf8_lossy(&item));
+            return;
+        };
+        let transport = Arc::clone(&self.transport_tx);
+        let commands = Arc::clone(&self.commands);
+        let reader_stopped = Arc::clone(&self.reader_stopped);
+        runtime.spawn(async move {
+            let mut transport = transport.lock().await;
+            {
+                let mut commands = commands.lock().await;
+                if reader_stopped.load(Ordering::Relaxed) {
+                    return;
+                }
+                commands.push_back(channel().0);
+            }
+            if let Err(e) = transport.send(&item[..]).await {
+                commands.lock().await.pop_back();
+                warn!("unable to send {:?}: {}", String::from_utf8_lossy(&item), e);
+            }
+        });
     }
 
     pub(crate) async fn new(
         stream: TcpStream,
-        password: impl ToString,
+        auth: Option<Arc<dyn AuthMethod>>,
         connection_type: EslConnectionType,
+        config: EslConfig,
     ) -> Result<Self, EslError> {
         // let sender = Arc::new(sender);
         let commands = Arc::new(Mutex::new(VecDeque::new()));
         let inner_commands = Arc::clone(&commands);
         let background_jobs = Arc::new(Mutex::new(HashMap::new()));
         let inner_background_jobs = Arc::clone(&background_jobs);
-        let esl_codec = EslCodec {};
+        let connected = Arc::new(AtomicBool::new(false));
+        let inner_connected = Arc::clone(&connected);
+        let reader_stopped = Arc::new(AtomicBool::new(false));
+        let inner_reader_stopped = Arc::clone(&reader_stopped);
+        let (events_tx, mut setup_events) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let events = events_tx.downgrade();
+        if config.tcp_nodelay {
+            stream.set_nodelay(true)?;
+        }
+        let esl_codec = config.codec();
+        let metrics = Arc::clone(&config.metrics);
+        let dtmf = DtmfBuffer::new(config.dtmf_buffer);
+        let holds = HoldState::default();
+        let bridge_peers = BridgePeers::default();
+        let heartbeats = HeartbeatWatch::default();
+        let stats = StatsCounters::default();
+        let unknown_frame_policy = config.unknown_frame_policy;
+        let on_disconnect = config.disconnect_hook();
         let (read_half, write_half) = tokio::io::split(stream);
-        let mut transport_rx = FramedRead::new(read_half, esl_codec.clone());
+        let mut transport_rx =
+            FramedRead::with_capacity(read_half, esl_codec.clone(), config.read_buffer_capacity);
         let transport_tx = Arc::new(Mutex::new(FramedWrite::new(write_half, esl_codec.clone())));
+        let mut auth_request = None;
         if connection_type == EslConnectionType::Inbound {
-            let event = transport_rx.next().await.ok_or_else(||EslError::InternalError("Didn't get auth request message".into()))??;
+            let event = transport_rx.next().await.ok_or_else(||EslError::InternalError("Didn't get auth request message".into()))??.into_event();
             if let Some(event_type) = event.headers.get("Content-Type") {
                 match event_type.as_str().unwrap() {
                     "auth/request" => trace!("Got auth request. Continuing."),
@@ -173,135 +474,385 @@ impl EslConnection {
                     other => {return Err(EslError::InternalError(format!("Invalid initial event type: {other}")))}
                 }
             }
+            auth_request = Some(event);
         }
         let mut connection = Self {
-            password: password.to_string(),
+            auth,
             commands,
             background_jobs,
             transport_tx,
-            connected: AtomicBool::new(false),
+            connected: Arc::clone(&connected),
+            closed: AtomicBool::new(false),
+            reader_stopped,
             call_uuid: None,
             connection_info: None,
+            subscriptions: std::sync::Mutex::default(),
+            filters: std::sync::Mutex::new(Vec::new()),
+            myevents_uuid: std::sync::Mutex::new(None),
+            playback_terminators: std::sync::Mutex::new(None),
+            events,
+            early_events: std::sync::Mutex::new(Vec::new()),
+            dtmf: dtmf.clone(),
+            holds: holds.clone(),
+            bridge_peers: bridge_peers.clone(),
+            heartbeats: heartbeats.clone(),
+            watchdog: None,
+            stats: stats.clone(),
+            config,
         };
+        let inner_transport_tx = Arc::clone(&connection.transport_tx);
         tokio::spawn(async move {
+            let mut protocol_error = false;
             loop {
-                if let Some(Ok(event)) = transport_rx.next().await {
-                    if let Some(event_type) = event.headers.get("Content-Type") {
-                        match event_type.as_str().unwrap() {
-                            "text/disconnect-notice" => {
-                                trace!("got disconnect notice");
-                                return;
-                            }
-                            "text/event-json" => {
-                                trace!("got event-json");
-                                let data = event
-                                    .body()
-                                    .clone()
-                                    .expect("Unable to get body of event-json");
-
-                                let event_body = parse_json_body(&data)
-                                    .expect("Unable to parse body of event-json");
-                                let job_uuid = event_body.get("Job-UUID");
-                                if let Some(job_uuid) = job_uuid {
-                                    let job_uuid = job_uuid.as_str().unwrap();
-                                    if let Some(tx) =
-                                        inner_background_jobs.lock().await.remove(job_uuid)
-                                    {
-                                        tx.send(event)
-                                            .expect("Unable to send channel message from bgapi");
-                                    }
-                                    trace!("continued");
+                let Some(response) = transport_rx.next().await else {
+                    trace!("connection closed");
+                    break;
+                };
+                stats.touch();
+                let response = match response {
+                    Ok(response) => response,
+                    Err(e) => {
+                        warn!("Unable to decode frame from freeswitch: {}", e);
+                        metrics.decode_error();
+                        continue;
+                    }
+                };
+                let event = match response {
+                    InboundResponse::Disconnect(_) => {
+                        trace!("got disconnect notice");
+                        break;
+                    }
+                    InboundResponse::Corrupt(event) => {
+                        warn!("Skipped corrupt frame from freeswitch: {:?}", event.headers());
+                        metrics.decode_error();
+                        let content_type =
+                            event.headers.get("Content-Type").and_then(|c| c.as_str());
+                        // a lost reply fails its command, so later replies stay correlated,
+                        // while any other frame must not take the slot of a reply in flight
+                        if matches!(content_type, Some("command/reply" | "api/response")) {
+                            let reply = InboundResponse::Corrupt(event);
+                            reply_to_command(&inner_commands, metrics.as_ref(), reply).await;
+                        }
+                        continue;
+                    }
+                    InboundResponse::Reply(event) => event,
+                    InboundResponse::Event(event) => {
+                        stats.event_received();
+                        // plain events only arrive before our json subscription
+                        let _ = events_tx.send(event);
+                        continue;
+                    }
+                    api @ InboundResponse::Api(_) => {
+                        reply_to_command(&inner_commands, metrics.as_ref(), api).await;
+                        continue;
+                    }
+                };
+                // frames without a Content-Type, e.g. a bare `+OK`, reply to the next command
+                let content_type = event.headers.get("Content-Type").and_then(|c| c.as_str());
+                if let Some(event_type) = content_type {
+                    match event_type {
+                        "text/event-json" => {
+                            trace!("got event-json");
+                            let parsed = match event.body() {
+                                Some(data) => parse_json_body(data),
+                                None => Err(EslError::InternalError(
+                                    "event-json without body".into(),
+                                )),
+                            };
+                            let event_body = match parsed {
+                                Ok(event_body) => event_body,
+                                Err(e) => {
+                                    warn!("Skipped event-json from freeswitch: {}", e);
+                                    metrics.decode_error();
                                     continue;
                                 }
-                                if let Some(application_uuid) = event_body.get("Application-UUID") {
-                                    let job_uuid = application_uuid.as_str().unwrap();
-                                    if let Some(event_name) = event_body.get("Event-Name") {
-                                        if let Some(event_name) = event_name.as_str() {
-                                            if event_name == "CHANNEL_EXECUTE_COMPLETE" {
-                                                if let Some(tx) = inner_background_jobs
-                                                    .lock()
-                                                    .await
-                                                    .remove(job_uuid)
-                                                {
-                                                    tx.send(event).expect(
-                                                        "Unable to send channel message from bgapi",
-                                                    );
-                                                }
-                                                trace!("continued");
-                                                trace!("got channel execute complete");
-                                            }
-                                        }
+                            };
+                            dtmf.record(&event_body);
+                            holds.record(&event_body);
+                            bridge_peers.record(&event_body);
+                            heartbeats.record(&event_body);
+                            stats.event_received();
+                            let job_uuid = event_body.get("Job-UUID").or_else(|| {
+                                // applications are correlated by the Event-UUID of execute
+                                let event_name = event_body.get("Event-Name")?.as_str()?;
+                                if event_name != "CHANNEL_EXECUTE_COMPLETE" {
+                                    return None;
+                                }
+                                event_body.get("Application-UUID")
+                            });
+                            let waiting = match job_uuid.and_then(|uuid| uuid.as_str()) {
+                                Some(job_uuid) => {
+                                    inner_background_jobs.lock().await.remove(job_uuid)
+                                }
+                                None => None,
+                            };
+                            match waiting {
+                                Some(tx) => {
+                                    if tx.send(event).is_err() {
+                                        trace!("receiver of job was dropped");
                                     }
                                 }
-                                continue;
+                                None => {
+                                    // fails only when no stream is listening
+                                    let _ = events_tx.send(event);
+                                }
                             }
-                            _ => {
-                                trace!("got another event {:?}", event);
+                            continue;
+                        }
+                        "command/reply" | "api/response" => {}
+                        other => {
+                            match unknown_frame_policy {
+                                UnknownFramePolicy::Ignore => {
+                                    warn!("Ignoring frame of unknown type {:?}", other);
+                                }
+                                UnknownFramePolicy::RouteToEvents => {
+                                    let _ = events_tx.send(event);
+                                }
+                                UnknownFramePolicy::Error => {
+                                    // it isn't a reply, so failing a waiting command with
+                                    // it would shift the replies of all later ones
+                                    warn!("Closing connection on unknown frame {:?}", other);
+                                    metrics.decode_error();
+                                    protocol_error = true;
+                                    break;
+                                }
                             }
+                            continue;
                         }
                     }
-                    if let Some(tx) = inner_commands.lock().await.pop_front() {
-                        tx.send(event).expect("msg");
-                    }
+                }
+                let reply = InboundResponse::Reply(event);
+                reply_to_command(&inner_commands, metrics.as_ref(), reply).await;
+            }
+            if protocol_error {
+                if let Err(e) = inner_transport_tx.lock().await.close().await {
+                    warn!("Unable to close connection: {}", e);
                 }
             }
+            inner_connected.store(false, Ordering::Relaxed);
+            // dropping the senders fails waiting commands with `Disconnected`
+            {
+                let mut commands = inner_commands.lock().await;
+                inner_reader_stopped.store(true, Ordering::Relaxed);
+                commands.clear();
+            }
+            inner_background_jobs.lock().await.clear();
+            on_disconnect();
         });
         match connection_type {
             EslConnectionType::Inbound => {
-                let auth_response = connection.auth().await?;
+                let request = auth_request.ok_or_else(|| {
+                    EslError::InternalError("Didn't get auth request message".into())
+                })?;
+                let auth_response = connection.auth(&request).await?;
                 trace!("auth_response {:?}", auth_response);
                 connection
                     .subscribe(vec!["BACKGROUND_JOB", "CHANNEL_EXECUTE_COMPLETE"])
                     .await?;
             }
+            EslConnectionType::InboundPreauthed => {
+                connection.connected.store(true, Ordering::Relaxed);
+                connection
+                    .subscribe(vec!["BACKGROUND_JOB", "CHANNEL_EXECUTE_COMPLETE"])
+                    .await?;
+            }
             EslConnectionType::Outbound => {
                 let response = connection.send_recv(b"connect").await?;
                 trace!("{:?}", response);
-                connection.connection_info = Some(response.headers().clone());
+                let content_type = response.headers.get("Content-Type").and_then(|c| c.as_str());
+                if content_type != Some("command/reply") {
+                    let content_type = content_type.unwrap_or_default().to_string();
+                    return Err(EslError::UnexpectedEvent(content_type));
+                }
+                let connection_info = ConnectionInfo::from(response.fields());
+                let channel_unique_id = connection_info.unique_id.clone().ok_or_else(|| {
+                    EslError::InternalError("connect reply without Channel-Unique-ID".into())
+                })?;
+                connection.connection_info = Some(connection_info);
+                connection.call_uuid = Some(channel_unique_id);
                 let response = connection
                     .subscribe(vec!["BACKGROUND_JOB", "CHANNEL_EXECUTE_COMPLETE"])
-                    .await?;
-                trace!("{:?}", response);
-                let response = connection.send_recv(b"myevents").await?;
-                trace!("{:?}", response);
-                let connection_info = connection.connection_info.as_ref().unwrap();
-
-                let channel_unique_id = connection_info
-                    .get("Channel-Unique-ID")
-                    .unwrap()
-                    .as_str()
-                    .unwrap();
-                connection.call_uuid = Some(channel_unique_id.to_string());
+                    .await;
+                connection.setup_step("subscribe", response)?;
+                let response = connection.send_recv(b"myevents").await;
+                connection.setup_step("myevents", response)?;
+            }
+        }
+        let mut early_events = Vec::new();
+        loop {
+            match setup_events.try_recv() {
+                Ok(event) => early_events.push(event),
+                Err(TryRecvError::Lagged(skipped)) => {
+                    warn!("dropped {} events received during setup", skipped)
+                }
+                Err(_) => break,
             }
         }
+        connection.early_events = std::sync::Mutex::new(early_events);
+        if let Some(interval) = connection.config.heartbeat_interval {
+            connection.watchdog = Some(
+                connection
+                    .heartbeats
+                    .spawn_watchdog(interval, Arc::clone(&connection.connected)),
+            );
+        }
+        connection.config.metrics.connected();
         Ok(connection)
     }
 
-    /// subscribes to given events
-    pub async fn subscribe(&self, events: Vec<&str>) -> Result<Event, EslError> {
-        let message = format!("event json {}", events.join(" "));
-        self.send_recv(message.as_bytes()).await
+    /// subscribes to given events in addition to the events subscribed before,
+    /// as freeswitch adds up `event` commands until [`EslConnection::unsubscribe`].
+    ///
+    /// Names are standard events, [`EventSubscription::Custom`] subclasses can be
+    /// mixed in and are sent after a single `CUSTOM`.
+    pub async fn subscribe<E: Into<EventSubscription>>(
+        &self,
+        events: impl IntoIterator<Item = E>,
+    ) -> Result<Event, EslError> {
+        let names = self.subscription_names(events)?;
+        let message = format!("event json {}", names.join(" "));
+        let response = self.send_recv(message.as_bytes()).await?;
+        let accepted = response
+            .reply_text()
+            .is_some_and(|reply_text| reply_text.starts_with("+OK"));
+        if accepted {
+            self.subscribed(&names);
+        }
+        Ok(response)
     }
 
-    pub(crate) async fn auth(&self) -> Result<String, EslError> {
-        let auth_response = self
-            .send_recv(format!("auth {}", self.password).as_bytes())
-            .await?;
-        let auth_headers = auth_response.headers();
-        let reply_text = auth_headers.get("Reply-Text").ok_or_else(|| {
-            EslError::InternalError("Reply-Text in auth request was not found".into())
-        })?;
-        let reply_text = reply_text.as_str().unwrap();
-        let (code, text) = parse_api_response(reply_text)?;
-        match code {
-            Code::Ok => {
+    /// Records `names` of an accepted `event` command, returning those not subscribed before
+    pub(crate) fn subscribed(&self, names: &[String]) -> Vec<String> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let missing = subscriptions.missing(names);
+        subscriptions.add(names);
+        self.heartbeats.expect(&subscriptions.names());
+        missing
+    }
+
+    /// unsubscribes from given events with `nixevent`, keeping the other subscriptions
+    pub async fn unsubscribe<E: Into<EventSubscription>>(
+        &self,
+        events: impl IntoIterator<Item = E>,
+    ) -> Result<Event, EslError> {
+        let events: Vec<EventSubscription> = events.into_iter().map(Into::into).collect();
+        let names = subscription_names(&events);
+        let message = format!("nixevent {}", names.join(" "));
+        let response = self.send_recv(message.as_bytes()).await?;
+        if response
+            .reply_text()
+            .is_some_and(|reply_text| reply_text.starts_with("+OK"))
+        {
+            self.unsubscribed(Some(&names));
+        }
+        Ok(response)
+    }
+
+    /// unsubscribes from all events with `noevents`
+    pub async fn unsubscribe_all(&self) -> Result<Event, EslError> {
+        let response = self.send_recv(b"noevents").await?;
+        if response
+            .reply_text()
+            .is_some_and(|reply_text| reply_text.starts_with("+OK"))
+        {
+            self.unsubscribed(None);
+        }
+        Ok(response)
+    }
+
+    /// Forgets `names`, or every event when `None`, after freeswitch dropped them
+    pub(crate) fn unsubscribed(&self, names: Option<&[String]>) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        match names {
+            Some(names) => subscriptions.remove(names),
+            None => subscriptions.clear(),
+        }
+        self.heartbeats.expect(&subscriptions.names());
+    }
+
+    /// Returns arguments of the `event` command, checked in strict mode
+    pub(crate) fn subscription_names<E: Into<EventSubscription>>(
+        &self,
+        events: impl IntoIterator<Item = E>,
+    ) -> Result<Vec<String>, EslError> {
+        let events: Vec<EventSubscription> = events.into_iter().map(Into::into).collect();
+        let names = subscription_names(&events);
+        if self.config.strict_events {
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            if let Some(name) = unknown_event(&names) {
+                return Err(EslError::UnknownEvent(name.to_string()));
+            }
+        }
+        Ok(names)
+    }
+
+    /// subscribes to CUSTOM events of the given subclasses, e.g. `conference::maintenance`
+    pub async fn subscribe_custom(&self, subclasses: &[&str]) -> Result<Event, EslError> {
+        let mut events = vec!["CUSTOM"];
+        events.extend_from_slice(subclasses);
+        self.subscribe(events).await
+    }
+
+    /// Returns events requested by all accepted subscribe calls, less the unsubscribed ones.
+    ///
+    /// Freeswitch acknowledges `event` commands without listing the events,
+    /// and silently ignores unknown event names, so this is the requested set.
+    pub fn subscribed_events(&self) -> Vec<String> {
+        self.subscriptions.lock().unwrap().names()
+    }
+
+    /// Checks reply of an outbound setup command, only warning in lenient mode
+    fn setup_step(&self, step: &str, response: Result<Event, EslError>) -> Result<(), EslError> {
+        trace!("{} {:?}", step, response);
+        let result = response.and_then(|response| {
+            match response.reply_text().and_then(|t| t.strip_prefix("-ERR")) {
+                Some(error) => Err(EslError::ApiError(error.trim().to_string())),
+                None => Ok(()),
+            }
+        });
+        match result {
+            Err(e) if self.config.lenient_setup => {
+                warn!("outbound {} failed, continuing: {}", step, e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    pub(crate) async fn auth(&self, request: &Event) -> Result<String, EslError> {
+        let mut attempt = 0;
+        loop {
+            let command = self.auth.as_ref().and_then(|auth| auth.auth_command(request));
+            let Some(command) = command else {
                 self.connected.store(true, Ordering::Relaxed);
-                Ok(text)
+                return Ok(String::new());
+            };
+            let auth_response = self.send_recv(command.as_bytes()).await?;
+            let auth_headers = auth_response.headers();
+            let reply_text = auth_headers.get("Reply-Text").ok_or_else(|| {
+                EslError::InternalError("Reply-Text in auth request was not found".into())
+            })?;
+            let reply_text = reply_text.as_str().unwrap();
+            let (code, text) = parse_api_response(reply_text)?;
+            match code {
+                Code::Ok => {
+                    self.connected.store(true, Ordering::Relaxed);
+                    return Ok(text);
+                }
+                // a wrong password is definitive, anything else may be load related
+                Code::Err if reply_text == "-ERR invalid" => return Err(EslError::AuthFailed),
+                Code::Err if attempt >= self.config.auth_retries => {
+                    return Err(EslError::AuthFailed)
+                }
+                Code::Err => warn!("auth rejected with {}, retrying", reply_text),
+                Code::Unknown => {
+                    return Err(EslError::InternalError(
+                        "Got unknown code in auth request".into(),
+                    ))
+                }
             }
-            Code::Err => Err(EslError::AuthFailed),
-            Code::Unknown => Err(EslError::InternalError(
-                "Got unknown code in auth request".into(),
-            )),
+            attempt += 1;
+            tokio::time::sleep(self.config.auth_retry_delay).await;
         }
     }
 
@@ -310,21 +861,109 @@ impl EslConnection {
         self.execute("hangup", reason).await
     }
 
-    /// executes application in freeswitch
+    /// executes application in freeswitch.
+    ///
+    /// Returns `AppError` when freeswitch rejects the application or its
+    /// `Application-Response` is an `-ERR`.
     pub async fn execute(&self, app_name: &str, app_args: &str) -> Result<Event, EslError> {
+        self.execute_with(app_name, app_args, &[]).await
+    }
+
+    /// executes application like [`EslConnection::execute`] with `event-lock: true`,
+    /// so applications queued after it wait until it finished
+    pub async fn execute_locked(&self, app_name: &str, app_args: &str) -> Result<Event, EslError> {
+        self.execute_with(app_name, app_args, &[("event-lock", "true")])
+            .await
+    }
+
+    async fn execute_with(
+        &self,
+        app_name: &str,
+        app_args: &str,
+        extra_headers: &[(&str, &str)],
+    ) -> Result<Event, EslError> {
+        let (_, rx) = self
+            .start_execute(app_name, app_args, extra_headers)
+            .await?;
+        execute_completion(app_name, rx).await
+    }
+
+    /// Sends `execute` of the application, returning its `Event-UUID` and the
+    /// receiver of its `CHANNEL_EXECUTE_COMPLETE`
+    pub(crate) async fn start_execute(
+        &self,
+        app_name: &str,
+        app_args: &str,
+        extra_headers: &[(&str, &str)],
+    ) -> Result<(String, Receiver<Event>), EslError> {
+        // fail before registering the completion, which would never arrive
+        self.ready_call_uuid()?;
         let event_uuid = uuid::Uuid::new_v4().to_string();
         let (tx, rx) = channel();
         self.background_jobs
             .lock()
             .await
             .insert(event_uuid.clone(), tx);
-        let call_uuid = self.call_uuid.as_ref().unwrap().clone();
-        let command  = format!("sendmsg {}\nexecute-app-name: {}\nexecute-app-arg: {}\ncall-command: execute\nEvent-UUID: {}",call_uuid,app_name,app_args,event_uuid);
-        let response = self.send_recv(command.as_bytes()).await?;
+        // freeswitch limits header lines, and header values can't span lines
+        let in_body = app_args.len() > MAX_APP_ARG_HEADER_LENGTH || app_args.contains('\n');
+        let mut headers = vec![("execute-app-name", app_name)];
+        if in_body {
+            headers.push(("content-type", "text/plain"));
+        } else {
+            headers.push(("execute-app-arg", app_args));
+        }
+        headers.push(("call-command", "execute"));
+        headers.push(("Event-UUID", &event_uuid));
+        headers.extend_from_slice(extra_headers);
+        let body = in_body.then_some(app_args);
+        let response = self.sendmsg_with_body(&headers, body).await?;
         trace!("inside execute {:?}", response);
-        let resp = rx.await?;
-        trace!("got response from channel {:?}", resp);
-        Ok(resp)
+        if let Some(error) = response.reply_text().and_then(|t| t.strip_prefix("-ERR")) {
+            self.background_jobs.lock().await.remove(&event_uuid);
+            return Err(EslError::AppError(format!("{}: {}", app_name, error.trim())));
+        }
+        Ok((event_uuid, rx))
+    }
+
+    /// Returns uuid of the controlled call, which is only set on outbound connections
+    pub(crate) fn ready_call_uuid(&self) -> Result<&str, EslError> {
+        self.call_uuid.as_deref().ok_or(EslError::NotReady)
+    }
+
+    /// sends sendmsg with given headers to the call in outbound mode
+    pub(crate) async fn sendmsg(&self, headers: &[(&str, &str)]) -> Result<Event, EslError> {
+        self.sendmsg_with_body(headers, None).await
+    }
+
+    /// sends sendmsg with given headers and an optional body after them
+    async fn sendmsg_with_body(
+        &self,
+        headers: &[(&str, &str)],
+        body: Option<&str>,
+    ) -> Result<Event, EslError> {
+        let mut command = format!("sendmsg {}", self.ready_call_uuid()?);
+        for (name, value) in headers {
+            command.push_str(&format!("\n{}: {}", name, value));
+        }
+        if let Some(body) = body {
+            push_body(&mut command, body);
+        }
+        self.send_recv(command.as_bytes()).await
+    }
+
+    /// sends call-command like `nomedia` to the call in outbound mode
+    pub async fn send_call_command(
+        &self,
+        command: &str,
+        extra_headers: &[(&str, &str)],
+    ) -> Result<Event, EslError> {
+        let mut headers = vec![("call-command", command)];
+        headers.extend_from_slice(extra_headers);
+        let response = self.sendmsg(&headers).await?;
+        if let Some(error) = response.reply_text().and_then(|t| t.strip_prefix("-ERR")) {
+            return Err(EslError::ApiError(error.trim().to_string()));
+        }
+        Ok(response)
     }
 
     /// answers call in outbound mode
@@ -333,14 +972,14 @@ impl EslConnection {
     }
 
     /// sends api command to freeswitch
+    ///
+    /// Api commands take all their input from `command`: freeswitch reads a body sent
+    /// with `Content-Length` off the socket, but only hands the command line to the api.
     pub async fn api(&self, command: &str) -> Result<String, EslError> {
-        let response = self.send_recv(format!("api {}", command).as_bytes()).await;
-        let event = response?;
-        let body = event
-            .body
-            .ok_or_else(|| EslError::InternalError("Didnt get body in api response".into()))?;
+        let body = self.api_body(command).await?;
+        let body = String::from_utf8_lossy(&body).to_string();
 
-        let (code, text) = parse_api_response(&body)?;
+        let (code, text) = parse_api_response(trim_body(&body))?;
         match code {
             Code::Ok => Ok(text),
             Code::Err => Err(EslError::ApiError(text)),
@@ -348,20 +987,77 @@ impl EslConnection {
         }
     }
 
+    /// sends api command to freeswitch, returning the raw body bytes.
+    ///
+    /// Unlike [`EslConnection::api`] the body isn't converted to UTF-8 or parsed,
+    /// only a body starting with `-ERR` is turned into an `ApiError`.
+    pub async fn api_bytes(&self, command: &str) -> Result<Vec<u8>, EslError> {
+        let body = self.api_body(command).await?;
+        if let Some(error) = body.strip_prefix(b"-ERR") {
+            let error = String::from_utf8_lossy(error);
+            return Err(EslError::ApiError(error.trim().to_string()));
+        }
+        Ok(body.to_vec())
+    }
+
+    /// sends api command and returns the body, without building an event
+    /// for the usual `api/response` frames
+    pub(crate) async fn api_body(&self, command: &str) -> Result<Bytes, EslError> {
+        let reply = self
+            .send_recv_within(format!("api {}", command).as_bytes(), self.config.api_timeout)
+            .await?;
+        match reply {
+            InboundResponse::Api(body) => Ok(body),
+            other => other
+                .into_event()
+                .raw_body
+                .ok_or_else(|| EslError::InternalError("Didnt get body in api response".into())),
+        }
+    }
+
     /// sends bgapi commands to freeswitch
     pub async fn bgapi(&self, command: &str) -> Result<String, EslError> {
+        let (_, body) = self.bgapi_with_job(command).await?;
+        Ok(body)
+    }
+
+    /// sends bgapi commands to freeswitch, returning the `Job-UUID` with the body
+    pub async fn bgapi_with_job(&self, command: &str) -> Result<(String, String), EslError> {
         trace!("Send bgapi {}", command);
         let job_uuid = uuid::Uuid::new_v4().to_string();
+        let message = format!("bgapi {}\nJob-UUID: {}", command, job_uuid);
+        let body = self
+            .background_job(&job_uuid, command, self.send_recv(message.as_bytes()))
+            .await?;
+        Ok((job_uuid, body))
+    }
+
+    /// Waits for the `BACKGROUND_JOB` of `job_uuid` started by `send`, returning its
+    /// body. The job is registered before `send` runs, so its event can't be missed.
+    pub(crate) async fn background_job<T>(
+        &self,
+        job_uuid: &str,
+        command: &str,
+        send: impl std::future::Future<Output = Result<T, EslError>>,
+    ) -> Result<String, EslError> {
         let (tx, rx) = channel();
         self.background_jobs
             .lock()
             .await
-            .insert(job_uuid.clone(), tx);
+            .insert(job_uuid.to_string(), tx);
 
-        self.send_recv(format!("bgapi {}\nJob-UUID: {}", command, job_uuid).as_bytes())
-            .await?;
-
-        let resp = rx.await?;
+        let resp = match send.await {
+            Ok(_) => within(self.config.bgapi_timeout, command.as_bytes(), rx).await,
+            Err(e) => Err(e),
+        };
+        let resp = match resp {
+            Ok(resp) => resp?,
+            Err(e) => {
+                // the job event won't be awaited anymore
+                self.background_jobs.lock().await.remove(job_uuid);
+                return Err(e);
+            }
+        };
         let body = resp
             .body()
             .clone()
@@ -369,13 +1065,13 @@ impl EslConnection {
 
         let body_hashmap = parse_json_body(&body)?;
 
-        let mut hsmp = resp.headers().clone();
+        let mut hsmp: HashMap<String, Value> = resp.headers().clone().into_iter().collect();
         hsmp.extend(body_hashmap);
         let body = hsmp
             .get("_body")
             .ok_or_else(|| EslError::InternalError("body was not found in event/json".into()))?;
         let body = body.as_str().unwrap();
-        let (code, text) = parse_api_response(body)?;
+        let (code, text) = parse_api_response(trim_body(body))?;
         match code {
             Code::Ok => Ok(text),
             Code::Err => Err(EslError::ApiError(text)),
@@ -383,18 +1079,94 @@ impl EslConnection {
         }
     }
 }
+
+/// Passes a reply to the oldest command waiting for one
+async fn reply_to_command(
+    commands: &Mutex<VecDeque<Sender<InboundResponse>>>,
+    metrics: &dyn Metrics,
+    reply: InboundResponse,
+) {
+    let mut commands = commands.lock().await;
+    if let Some(tx) = commands.pop_front() {
+        metrics.reply_received();
+        metrics.in_flight(commands.len());
+        if tx.send(reply).is_err() {
+            trace!("receiver of command was dropped");
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Forgets a background job or application when dropped, see [`EslConnection::job_guard`]
+pub(crate) struct JobGuard<'a> {
+    connection: &'a EslConnection,
+    uuid: String,
+}
+
+impl JobGuard<'_> {
+    pub(crate) fn uuid(&self) -> &str {
+        &self.uuid
+    }
+}
+
+impl Drop for JobGuard<'_> {
+    fn drop(&mut self) {
+        // a completion which arrived already removed the job
+        self.connection.forget_job(&self.uuid);
+    }
+}
+
+/// Waits for the `CHANNEL_EXECUTE_COMPLETE` of an application started with
+/// `start_execute`, turning an `-ERR` response into `AppError`
+pub(crate) async fn execute_completion(
+    app_name: &str,
+    rx: Receiver<Event>,
+) -> Result<Event, EslError> {
+    let resp = rx.await?;
+    trace!("got response from channel {:?}", resp);
+    let fields = resp.fields();
+    let app_response = fields
+        .get("Application-Response")
+        .and_then(|response| response.as_str())
+        .unwrap_or_default();
+    if let Some(error) = app_response.strip_prefix("-ERR") {
+        return Err(EslError::AppError(format!("{}: {}", app_name, error.trim())));
+    }
+    Ok(resp)
+}
+
+/// Awaits `future`, failing once `timeout` passes while waiting for `command`
+async fn within<F: std::future::Future>(
+    timeout: Option<Duration>,
+    command: &[u8],
+    future: F,
+) -> Result<F::Output, EslError> {
+    let Some(timeout) = timeout else {
+        return Ok(future.await);
+    };
+    tokio::time::timeout(timeout, future).await.map_err(|_| {
+        let command = String::from_utf8_lossy(command);
+        EslError::Timeout {
+            command: command.lines().next().unwrap_or_default().to_string(),
+            elapsed: timeout,
+        }
+    })
+}
+
+/// Appends `body` to the headers of a message in a `content-length` block.
+///
+/// The length is exactly that of the body: like libesl, the blank line the codec ends
+/// every message with follows outside it, and freeswitch skips it before the next command.
+fn push_body(message: &mut String, body: &str) {
+    message.push_str(&format!("\ncontent-length: {}\n\n{}", body.len(), body));
+}
+
 fn parse_api_response(body: &str) -> Result<(Code, String), EslError> {
-    let space_index = body
-        .find(char::is_whitespace)
-        .ok_or_else(|| EslError::InternalError("Unable to find space index".into()))?;
+    // single word bodies like the `true` of uuid_exists have no text
+    let space_index = body.find(char::is_whitespace).unwrap_or(body.len());
     let code = &body[..space_index];
-    let text_start = space_index + 1;
-    let body_length = body.len();
-    let text = if text_start < body_length {
-        body[text_start..(body_length - 1)].to_string()
-    } else {
-        String::new()
-    };
+    // the text may start on the next line, its own newlines are kept
+    let text = body[space_index..].trim_start().to_string();
     let code = code.parse_code()?;
     Ok((code, text))
 }
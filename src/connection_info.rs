@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::event::field_string;
+use crate::io::url_decode;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Channel data sent by freeswitch in reply to `connect` in outbound mode
+pub struct ConnectionInfo {
+    /// Value of `Channel-Name` e.g. `sofia/internal/1000@10.0.0.1:5060`
+    pub channel_name: Option<String>,
+    /// Value of `Channel-Unique-ID`
+    pub unique_id: Option<String>,
+    /// Value of `Call-Direction`
+    pub direction: Option<String>,
+    /// Value of `Caller-Caller-ID-Name`
+    pub caller_id_name: Option<String>,
+    /// Value of `Caller-Caller-ID-Number`
+    pub caller_id_number: Option<String>,
+    /// Value of `Caller-Destination-Number`
+    pub destination_number: Option<String>,
+    /// Value of `Caller-Context`
+    pub context: Option<String>,
+    /// Value of `Channel-State`
+    pub channel_state: Option<String>,
+    raw: HashMap<String, Value>,
+}
+
+impl ConnectionInfo {
+    /// Returns all channel data sent by freeswitch
+    pub fn raw(&self) -> &HashMap<String, Value> {
+        &self.raw
+    }
+}
+
+impl From<HashMap<String, Value>> for ConnectionInfo {
+    fn from(mut raw: HashMap<String, Value>) -> Self {
+        // freeswitch url encodes the channel data of the connect reply
+        for value in raw.values_mut() {
+            if let Some(text) = value.as_str() {
+                *value = Value::from(url_decode(text));
+            }
+        }
+        Self {
+            channel_name: field_string(&raw, "Channel-Name"),
+            unique_id: field_string(&raw, "Channel-Unique-ID"),
+            direction: field_string(&raw, "Call-Direction"),
+            caller_id_name: field_string(&raw, "Caller-Caller-ID-Name"),
+            caller_id_number: field_string(&raw, "Caller-Caller-ID-Number"),
+            destination_number: field_string(&raw, "Caller-Destination-Number"),
+            context: field_string(&raw, "Caller-Context"),
+            channel_state: field_string(&raw, "Channel-State"),
+            raw,
+        }
+    }
+}
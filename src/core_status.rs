@@ -0,0 +1,75 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use crate::{EslConnection, EslError};
+
+/// Labeled patterns of the `status` lines, each matched anywhere in the reply
+/// since the wording and order changed between freeswitch versions
+static PATTERNS: LazyLock<Vec<(&str, Regex)>> = LazyLock::new(|| {
+    [
+        ("since_startup", r"(\d+) session\(s\) since startup"),
+        ("current", r"(?m)^\s*(\d+) session\(s\)(?: - | \d+/\d+\s*$)"),
+        ("peak", r"session\(s\) - peak (\d+)"),
+        ("per_sec", r"(?m)^\s*(\d+) session\(s\) per Sec"),
+        ("per_sec", r"(?m)^\s*\d+ session\(s\) (\d+)/\d+\s*$"),
+        ("max_per_sec", r"per Sec out of max (\d+)"),
+        ("max_per_sec", r"(?m)^\s*\d+ session\(s\) \d+/(\d+)\s*$"),
+        ("max", r"(\d+) session\(s\) max"),
+        ("idle_cpu", r"idle cpu [\d.]+/([\d.]+)"),
+    ]
+    .into_iter()
+    .map(|(label, pattern)| (label, Regex::new(pattern).unwrap()))
+    .collect()
+});
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+/// Session counters and load parsed from the reply of `api status`.
+///
+/// Lines missing in the reply of a freeswitch version are `None`.
+pub struct CoreStatus {
+    /// Sessions created since freeswitch started
+    pub sessions_since_startup: Option<u64>,
+    /// Sessions currently active
+    pub sessions: Option<u64>,
+    /// Most sessions active at once since startup
+    pub peak_sessions: Option<u64>,
+    /// Sessions currently created per second
+    pub sessions_per_sec: Option<u64>,
+    /// Limit of sessions created per second
+    pub max_sessions_per_sec: Option<u64>,
+    /// Limit of active sessions
+    pub max_sessions: Option<u64>,
+    /// Idle CPU in percent
+    pub idle_cpu: Option<f64>,
+}
+
+impl From<&str> for CoreStatus {
+    fn from(reply: &str) -> Self {
+        let find = |label: &str| {
+            PATTERNS
+                .iter()
+                .filter(|(name, _)| *name == label)
+                .find_map(|(_, pattern)| pattern.captures(reply)?.get(1))
+                .map(|value| value.as_str())
+        };
+        let number = |label: &str| find(label)?.parse().ok();
+        Self {
+            sessions_since_startup: number("since_startup"),
+            sessions: number("current"),
+            peak_sessions: number("peak"),
+            sessions_per_sec: number("per_sec"),
+            max_sessions_per_sec: number("max_per_sec"),
+            max_sessions: number("max"),
+            idle_cpu: find("idle_cpu").and_then(|value| value.parse().ok()),
+        }
+    }
+}
+
+impl EslConnection {
+    /// Returns session counters and load reported by `api status`
+    pub async fn core_status(&self) -> Result<CoreStatus, EslError> {
+        let reply = self.api("status").await?;
+        Ok(CoreStatus::from(reply.as_str()))
+    }
+}
@@ -1,23 +1,191 @@
 use std::collections::HashMap;
+use std::future::Future;
 
+use futures::future::{select, Either};
 use serde_json::Value;
+use tokio_stream::StreamExt;
+use tracing::warn;
 
 const PLAY_AND_GET_DIGITS_APP: &str = "play_and_get_digits";
 const PLAYBACK_APP: &str = "playback";
+/// Schemes of remote files freeswitch can play with mod_httapi, mod_http_cache and mod_shout
+const PLAYBACK_URL_SCHEMES: &[&str] = &["http://", "https://", "http_cache://", "shout://"];
 
+use crate::event::field_string;
+use crate::originate::parse_task_id;
 use crate::{EslConnection, EslError, Event};
 
+/// Hangup scheduled by [`EslConnection::with_max_duration`], removed on drop
+struct ScheduledHangup<'a> {
+    connection: &'a EslConnection,
+    task_id: Option<u32>,
+}
+
+impl ScheduledHangup<'_> {
+    /// Removes the task and waits for the reply, instead of in the background on drop
+    async fn cancel(&mut self) {
+        let Some(task_id) = self.task_id.take() else {
+            return;
+        };
+        // fails when the hangup already fired and the call is gone
+        let removed = self.connection.api(&format!("sched_del {}", task_id)).await;
+        if let Err(e) = removed {
+            warn!("unable to cancel scheduled hangup task {}: {}", task_id, e);
+        }
+    }
+}
+
+impl Drop for ScheduledHangup<'_> {
+    fn drop(&mut self) {
+        if let Some(task_id) = self.task_id {
+            let message = format!("api sched_del {}", task_id);
+            self.connection.send_detached(message.into_bytes());
+        }
+    }
+}
+
 impl EslConnection {
     /// plays file in call during outbound mode
     pub async fn playback(&self, file_path: &str) -> Result<Event, EslError> {
         self.execute(PLAYBACK_APP, file_path).await
     }
 
+    /// plays a remote file in outbound mode, `url` must be http(s), `http_cache` or `shout`
+    pub async fn playback_url(&self, url: &str) -> Result<Event, EslError> {
+        self.playback(playback_url(url)?).await
+    }
+
+    /// plays a remote http(s) file like [`EslConnection::playback_url`], through
+    /// `http_cache://` so freeswitch downloads it only once
+    pub async fn playback_url_cached(&self, url: &str) -> Result<Event, EslError> {
+        let url = playback_url(url)?;
+        if url.starts_with("shout://") {
+            return Err(EslError::InvalidArgument(format!(
+                "{} can't be played through http_cache",
+                url
+            )));
+        }
+        if url.starts_with("http_cache://") {
+            return self.playback(url).await;
+        }
+        self.playback(&format!("http_cache://{}", url)).await
+    }
+
+    /// bridges the call in outbound mode, calling `on_answer` as soon as the B-leg answers.
+    ///
+    /// The answer is seen by the `CHANNEL_BRIDGE` event of the call, or by a
+    /// `CHANNEL_ANSWER` of the B-leg when such events are subscribed. Returns the
+    /// completion of `bridge` like [`EslConnection::execute`], i.e. once the bridge ends.
+    pub async fn bridge_with_on_answer(
+        &self,
+        bridge_string: &str,
+        on_answer: impl FnOnce(),
+    ) -> Result<Event, EslError> {
+        let events = self.events_filtered(&["CHANNEL_ANSWER", "CHANNEL_BRIDGE"]);
+        let answered = async {
+            let mut events = std::pin::pin!(events);
+            while let Some(event) = events.next().await {
+                if self.is_b_leg_answer(&event) {
+                    return true;
+                }
+            }
+            false
+        };
+        let bridge = self.execute("bridge", bridge_string);
+        match select(std::pin::pin!(answered), std::pin::pin!(bridge)).await {
+            Either::Left((answered, bridge)) => {
+                if answered {
+                    on_answer();
+                }
+                bridge.await
+            }
+            Either::Right((result, _)) => result,
+        }
+    }
+
+    fn is_b_leg_answer(&self, event: &Event) -> bool {
+        let fields = event.fields();
+        let call_uuid = self.call_uuid.as_deref();
+        match field_string(&fields, "Event-Name").as_deref() {
+            Some("CHANNEL_BRIDGE") => field_string(&fields, "Unique-ID").as_deref() == call_uuid,
+            Some("CHANNEL_ANSWER") => {
+                field_string(&fields, "Other-Leg-Unique-ID").as_deref() == call_uuid
+                    && field_string(&fields, "Unique-ID").as_deref() != call_uuid
+            }
+            _ => false,
+        }
+    }
+
+    /// runs `app`, e.g. a `playback`, `bridge` or `conference`, with a scheduled hangup
+    /// after `secs` in outbound mode, so a stuck application can't hold the call forever.
+    ///
+    /// The hangup is scheduled as a `uuid_kill` task, as `sched_hangup` doesn't return
+    /// its task id. Once `app` finished only that task is removed with `sched_del`,
+    /// other tasks scheduled for the call are kept. If the returned future is dropped
+    /// before, the task is removed in the background.
+    pub async fn with_max_duration<T>(
+        &self,
+        secs: u32,
+        app: impl Future<Output = Result<T, EslError>>,
+    ) -> Result<T, EslError> {
+        let uuid = self.ready_call_uuid()?;
+        let response = self
+            .api(&format!(
+                "sched_api +{} none uuid_kill {} ALLOTTED_TIMEOUT",
+                secs, uuid
+            ))
+            .await?;
+        let mut hangup = ScheduledHangup {
+            connection: self,
+            task_id: Some(parse_task_id(&response)?),
+        };
+        let result = app.await;
+        hangup.cancel().await;
+        result
+    }
+
     /// record_session during outbound mode
     pub async fn record_session(&self, file_path: &str) -> Result<Event, EslError> {
         self.execute("record_session", file_path).await
     }
 
+    /// masks audio of an active `record_session` recording with silence.
+    ///
+    /// Used to pause recording while sensitive input like card numbers is
+    /// collected, as required for PCI compliance. `file_path` must be the path
+    /// passed to [`EslConnection::record_session`].
+    pub async fn record_session_mask(&self, file_path: &str) -> Result<Event, EslError> {
+        self.execute("record_session_mask", file_path).await
+    }
+
+    /// resumes recording audio masked by [`EslConnection::record_session_mask`]
+    pub async fn record_session_unmask(&self, file_path: &str) -> Result<Event, EslError> {
+        self.execute("record_session_unmask", file_path).await
+    }
+
+    /// starts watching the call for the tone `name` described by `tone_spec`.
+    ///
+    /// `flags` selects the direction (`r` and/or `w`) and `timeout_ms` of 0
+    /// watches until the call ends. When the tone is heard `app` is executed
+    /// with `app_args` and a `DETECTED_TONE` event is sent, which arrives on
+    /// [`EslConnection::events`].
+    pub async fn tone_detect(
+        &self,
+        name: &str,
+        tone_spec: &str,
+        flags: &str,
+        timeout_ms: u32,
+        app: &str,
+        app_args: &str,
+    ) -> Result<Event, EslError> {
+        let timeout = match timeout_ms {
+            0 => "0".to_string(),
+            millis => format!("+{}", millis),
+        };
+        let args = format!("{name} {tone_spec} {flags} {timeout} {app} {app_args}");
+        self.execute("tone_detect", args.trim_end()).await
+    }
+
     /// send dtmf during outbound mode
     pub async fn send_dtmf(&self, dtmf_str: &str) -> Result<Event, EslError> {
         self.execute("send_dtmf", dtmf_str).await
@@ -39,6 +207,22 @@ impl EslConnection {
         self.execute("set", &args).await
     }
 
+    /// sets DTMF digits which stop media apps for the rest of the session.
+    ///
+    /// `none` disables stopping media apps with DTMF.
+    pub async fn set_playback_terminators(&self, terminators: &str) -> Result<Event, EslError> {
+        let event = self
+            .set_variable("playback_terminators", terminators)
+            .await?;
+        *self.playback_terminators.lock().unwrap() = Some(terminators.to_string());
+        Ok(event)
+    }
+
+    /// returns playback terminators set with [`EslConnection::set_playback_terminators`]
+    pub fn playback_terminators(&self) -> Option<String> {
+        self.playback_terminators.lock().unwrap().clone()
+    }
+
     ///add  a freeswitch log
     pub async fn fs_log(&self, loglevel: &str, msg: &str) -> Result<Event, EslError> {
         let args = format!("{} {}", loglevel, msg);
@@ -62,7 +246,7 @@ impl EslConnection {
             "{min} {max} {tries} {timeout} {terminators} {file} {invalid_file} {variable_name}",
         );
         let data = self.execute(PLAY_AND_GET_DIGITS_APP, &app_args).await?;
-        let body = data.body.as_ref().unwrap();
+        let body = data.body().as_ref().unwrap();
         let body = parse_json_body(body).unwrap();
         let result = body.get(&format!("variable_{}", variable_name));
         let Some(digit) = result else {
@@ -76,3 +260,18 @@ impl EslConnection {
 fn parse_json_body(body: &str) -> Result<HashMap<String, Value>, EslError> {
     Ok(serde_json::from_str(body)?)
 }
+
+/// Returns `url` when its scheme is one freeswitch plays remote files from
+fn playback_url(url: &str) -> Result<&str, EslError> {
+    let valid = PLAYBACK_URL_SCHEMES.iter().any(|scheme| {
+        url.strip_prefix(scheme)
+            .is_some_and(|rest| !rest.is_empty())
+    });
+    if !valid || url.contains(char::is_whitespace) {
+        return Err(EslError::InvalidArgument(format!(
+            "unsupported playback url {:?}",
+            url
+        )));
+    }
+    Ok(url)
+}
@@ -0,0 +1,89 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio_stream::StreamExt;
+
+use crate::event::field_string;
+use crate::{EslConnection, EslError};
+
+#[derive(Debug, Clone, Default)]
+/// Last digits of the `DTMF` events of a connection, filled by its reader task
+pub(crate) struct DtmfBuffer {
+    digits: Arc<Mutex<VecDeque<char>>>,
+    capacity: usize,
+}
+
+impl DtmfBuffer {
+    /// Creates buffer keeping up to `capacity` digits, recording nothing when 0
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            digits: Arc::default(),
+            capacity,
+        }
+    }
+
+    /// Appends the `DTMF-Digit` of a `DTMF` event, ignoring other events and
+    /// dropping the oldest digit when full
+    pub(crate) fn record(&self, fields: &HashMap<String, Value>) {
+        if self.capacity == 0 || fields.get("Event-Name").and_then(Value::as_str) != Some("DTMF") {
+            return;
+        }
+        let Some(digit) = fields.get("DTMF-Digit").and_then(Value::as_str) else {
+            return;
+        };
+        let mut digits = self.digits.lock().unwrap();
+        for digit in digit.chars() {
+            if digits.len() == self.capacity {
+                digits.pop_front();
+            }
+            digits.push_back(digit);
+        }
+    }
+
+    fn peek(&self) -> String {
+        self.digits.lock().unwrap().iter().collect()
+    }
+
+    fn drain(&self) -> String {
+        self.digits.lock().unwrap().drain(..).collect()
+    }
+}
+
+impl EslConnection {
+    /// Returns DTMF digits pressed since the last [`EslConnection::drain_dtmf`] and clears them.
+    ///
+    /// Digits are only collected when enabled with [`crate::EslConfig::dtmf_buffer`],
+    /// from `DTMF` events, which outbound connections receive for their call and
+    /// inbound connections only after subscribing to them.
+    pub fn drain_dtmf(&self) -> String {
+        self.dtmf.drain()
+    }
+
+    /// Returns DTMF digits like [`EslConnection::drain_dtmf`] without clearing them
+    pub fn peek_dtmf(&self) -> String {
+        self.dtmf.peek()
+    }
+
+    /// Waits up to `timeout` for the next `DTMF` event and returns its digit.
+    ///
+    /// Only keys pressed after the call are seen, `None` means none was pressed in time.
+    pub async fn wait_for_dtmf(&self, timeout: Duration) -> Result<Option<char>, EslError> {
+        let mut events = Box::pin(self.events_filtered(&["DTMF"]));
+        let digit = async {
+            while let Some(event) = events.next().await {
+                let digit = field_string(&event.fields(), "DTMF-Digit")
+                    .and_then(|digit| digit.chars().next());
+                if digit.is_some() {
+                    return Ok(digit);
+                }
+            }
+            Err(EslError::Disconnected)
+        };
+        match tokio::time::timeout(timeout, digit).await {
+            Ok(digit) => digit,
+            Err(_) => Ok(None),
+        }
+    }
+}
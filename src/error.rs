@@ -1,4 +1,8 @@
-use std::num::ParseIntError;
+use std::net::AddrParseError;
+use std::num::{ParseFloatError, ParseIntError};
+use std::str::{ParseBoolError, Utf8Error};
+use std::string::FromUtf8Error;
+use std::time::Duration;
 
 use thiserror::Error;
 
@@ -21,8 +25,92 @@ pub enum EslError {
     #[error("")]
     CodeParseError(),
 
+    #[error("{0}")]
+    AppError(String),
+
     #[error("Didnt get any digits")]
     NoInput,
+
+    #[error("Unexpected event {0:?}")]
+    UnexpectedEvent(String),
+
+    #[error("Unknown event name {0:?}")]
+    UnknownEvent(String),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("Connection is closed")]
+    NotConnected,
+
+    #[error("Connection closed before the reply arrived")]
+    Disconnected,
+
+    #[error("Connection has no call to control")]
+    NotReady,
+
+    #[error("{command} timed out after {elapsed:?}")]
+    Timeout { command: String, elapsed: Duration },
+
+    /// Message of an error from a handler, see [`EslError::other`]
+    #[error("{0}")]
+    Other(String),
+}
+
+impl EslError {
+    /// Wraps any error, e.g. from the I/O or parsing of an outbound handler.
+    ///
+    /// Only the message is kept, as `EslError` is `Clone`, `Eq`, `Ord` and `Hash`,
+    /// which a boxed source error can't be.
+    pub fn other(error: impl std::error::Error) -> Self {
+        Self::Other(error.to_string())
+    }
+
+    /// Returns the kind of an `ApiError`, whose raw reason is kept in the variant
+    pub fn response_code(&self) -> Option<ResponseCode> {
+        match self {
+            Self::ApiError(reason) => Some(ResponseCode::from(reason.as_str())),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+/// Common reasons of an [`EslError::ApiError`]
+pub enum ResponseCode {
+    /// Channel of a `uuid_*` command doesn't exist
+    NoSuchChannel,
+    /// Arguments of the command are invalid, including `-USAGE` replies
+    InvalidSyntax,
+    /// Command isn't allowed for this connection
+    PermissionDenied,
+    /// Api command doesn't exist
+    CommandNotFound,
+    /// Any other reason, e.g. a hangup cause of `originate`
+    Other,
+}
+
+impl From<&str> for ResponseCode {
+    fn from(reason: &str) -> Self {
+        // freeswitch mixes `No such channel!` and `NO_SUCH_CHANNEL` style reasons
+        let reason = reason
+            .trim()
+            .trim_start_matches("-ERR")
+            .to_uppercase()
+            .replace([' ', '-'], "_");
+        if reason.contains("NO_SUCH_CHANNEL") {
+            Self::NoSuchChannel
+        } else if reason.contains("INVALID_SYNTAX") || reason.starts_with("_USAGE") {
+            Self::InvalidSyntax
+        } else if reason.contains("PERMISSION_DENIED") {
+            Self::PermissionDenied
+        } else if reason.contains("COMMAND_NOT_FOUND") {
+            Self::CommandNotFound
+        } else {
+            Self::Other
+        }
+    }
 }
 
 impl From<std::io::Error> for EslError {
@@ -31,8 +119,8 @@ impl From<std::io::Error> for EslError {
     }
 }
 impl From<tokio::sync::oneshot::error::RecvError> for EslError {
-    fn from(error: tokio::sync::oneshot::error::RecvError) -> Self {
-        Self::InternalError(error.to_string())
+    fn from(_: tokio::sync::oneshot::error::RecvError) -> Self {
+        Self::Disconnected
     }
 }
 impl From<serde_json::Error> for EslError {
@@ -42,6 +130,31 @@ impl From<serde_json::Error> for EslError {
 }
 impl From<ParseIntError> for EslError {
     fn from(error: ParseIntError) -> Self {
-        Self::InternalError(error.to_string())
+        Self::other(error)
+    }
+}
+impl From<ParseFloatError> for EslError {
+    fn from(error: ParseFloatError) -> Self {
+        Self::other(error)
+    }
+}
+impl From<ParseBoolError> for EslError {
+    fn from(error: ParseBoolError) -> Self {
+        Self::other(error)
+    }
+}
+impl From<Utf8Error> for EslError {
+    fn from(error: Utf8Error) -> Self {
+        Self::other(error)
+    }
+}
+impl From<FromUtf8Error> for EslError {
+    fn from(error: FromUtf8Error) -> Self {
+        Self::other(error)
+    }
+}
+impl From<AddrParseError> for EslError {
+    fn from(error: AddrParseError) -> Self {
+        Self::other(error)
     }
 }
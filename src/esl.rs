@@ -1,11 +1,29 @@
+use std::sync::Arc;
+
 use tokio::net::TcpStream;
 
-use crate::{connection::EslConnection, EslError};
+use crate::auth::{AuthMethod, PasswordAuth};
+use crate::{connection::EslConnection, EslConfig, EslError};
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum EslConnectionType {
     Inbound,
+    InboundPreauthed,
     Outbound,
 }
+
+#[derive(Debug)]
+/// Inbound stream which was already authenticated outside of this crate
+pub struct Preauthenticated(TcpStream);
+
+impl Preauthenticated {
+    /// Marks `stream` as authenticated.
+    ///
+    /// The caller must have read the `auth/request` and received `+OK accepted`
+    /// for its `auth` command, with no other bytes left unread on the stream.
+    pub fn assume(stream: TcpStream) -> Self {
+        Self(stream)
+    }
+}
 /// Esl struct with inbound and outbound method.
 pub struct Esl;
 impl Esl {
@@ -14,11 +32,52 @@ impl Esl {
         stream: TcpStream,
         password: impl ToString,
     ) -> Result<EslConnection, EslError> {
-        EslConnection::new(stream, password, EslConnectionType::Inbound).await
+        Self::inbound_with_config(stream, password, EslConfig::default()).await
+    }
+
+    /// Creates new inbound connection to freeswitch with given options
+    pub async fn inbound_with_config(
+        stream: TcpStream,
+        password: impl ToString,
+        config: EslConfig,
+    ) -> Result<EslConnection, EslError> {
+        Self::inbound_with_auth(stream, PasswordAuth::new(password), config).await
+    }
+
+    /// Creates new inbound connection which answers the auth request with `auth`
+    pub async fn inbound_with_auth(
+        stream: TcpStream,
+        auth: impl AuthMethod + 'static,
+        config: EslConfig,
+    ) -> Result<EslConnection, EslError> {
+        let auth: Arc<dyn AuthMethod> = Arc::new(auth);
+        EslConnection::new(stream, Some(auth), EslConnectionType::Inbound, config).await
+    }
+
+    /// Creates new inbound connection on a stream which is already authenticated
+    pub async fn inbound_preauthed(
+        stream: Preauthenticated,
+        config: EslConfig,
+    ) -> Result<EslConnection, EslError> {
+        EslConnection::new(
+            stream.0,
+            None,
+            EslConnectionType::InboundPreauthed,
+            config,
+        )
+        .await
     }
 
     /// Creates new server for outbound connection
     pub async fn outbound(stream: TcpStream) -> Result<EslConnection, EslError> {
-        EslConnection::new(stream, "None", EslConnectionType::Outbound).await
+        Self::outbound_with_config(stream, EslConfig::default()).await
+    }
+
+    /// Creates new server for outbound connection with given options
+    pub async fn outbound_with_config(
+        stream: TcpStream,
+        config: EslConfig,
+    ) -> Result<EslConnection, EslError> {
+        EslConnection::new(stream, None, EslConnectionType::Outbound, config).await
     }
 }
@@ -1,20 +1,207 @@
 use std::collections::HashMap;
+use std::sync::OnceLock;
 
+use bytes::Bytes;
+use indexmap::IndexMap;
 use serde_json::Value;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Number of characters of a value shown by [`Event::to_pretty_string`]
+const PRETTY_VALUE_LIMIT: usize = 120;
+
+#[derive(Clone)]
 /// Structure of event returned from freeswitch
 pub struct Event {
-    pub(crate) headers: HashMap<String, Value>,
-    pub(crate) body: Option<String>,
+    pub(crate) headers: IndexMap<String, Value>,
+    /// text of `raw_body` with invalid UTF-8 replaced, converted on first use
+    body: OnceLock<Option<String>>,
+    /// body exactly as received
+    pub(crate) raw_body: Option<Bytes>,
+    /// whole frame as received, only kept with [`crate::EslConfig::retain_raw_frames`]
+    pub(crate) raw_frame: Option<Bytes>,
+}
+
+impl std::fmt::Debug for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Event")
+            .field("headers", &self.headers)
+            .field("body", self.body())
+            .field("raw_body", &self.raw_body)
+            .field("raw_frame", &self.raw_frame)
+            .finish()
+    }
 }
+
+// the text body is derived from the raw one, whether converted yet or not
+impl PartialEq for Event {
+    fn eq(&self, other: &Self) -> bool {
+        self.headers == other.headers
+            && self.raw_body == other.raw_body
+            && self.raw_frame == other.raw_frame
+    }
+}
+
+impl Eq for Event {}
+
 impl Event {
-    /// Returns header from event
-    pub fn headers(&self) -> &HashMap<String, Value> {
+    pub(crate) fn new(
+        headers: IndexMap<String, Value>,
+        raw_body: Option<Bytes>,
+        raw_frame: Option<Bytes>,
+    ) -> Self {
+        Self {
+            headers,
+            body: OnceLock::new(),
+            raw_body,
+            raw_frame,
+        }
+    }
+
+    /// Returns headers from event in the order they were received
+    pub fn headers(&self) -> &IndexMap<String, Value> {
         &self.headers
     }
     /// Returns body from event
     pub fn body(&self) -> &Option<String> {
-        &self.body
+        self.body.get_or_init(|| {
+            let raw_body = self.raw_body.as_ref()?;
+            Some(String::from_utf8_lossy(raw_body).into_owned())
+        })
+    }
+
+    /// Returns body without the single trailing newline freeswitch ends most bodies with
+    pub fn body_trimmed(&self) -> Option<&str> {
+        self.body().as_deref().map(trim_body)
+    }
+
+    /// Returns body bytes without any UTF-8 conversion
+    pub fn body_bytes(&self) -> Option<&[u8]> {
+        self.raw_body.as_deref()
+    }
+
+    /// Returns headers and body exactly as received, when raw frames are retained
+    pub fn raw_frame(&self) -> Option<&[u8]> {
+        self.raw_frame.as_deref()
+    }
+
+    /// Returns `Reply-Text` header of a command reply
+    pub fn reply_text(&self) -> Option<&str> {
+        self.headers.get("Reply-Text")?.as_str()
+    }
+
+    /// Returns `Event-Name` of the event
+    pub fn event_name(&self) -> Option<String> {
+        if let Some(name) = self.headers.get("Event-Name") {
+            return name.as_str().map(|name| name.to_string());
+        }
+        let body = self.body().as_ref()?;
+        let fields: HashMap<String, Value> = serde_json::from_str(body).ok()?;
+        field_string(&fields, "Event-Name")
+    }
+
+    /// Renders headers sorted by name followed by the body, for logging.
+    ///
+    /// Values longer than 120 characters are cut with an ellipsis and their length.
+    pub fn to_pretty_string(&self) -> String {
+        let mut headers: Vec<_> = self.headers.iter().collect();
+        headers.sort_by(|a, b| a.0.cmp(b.0));
+        let mut pretty = String::new();
+        for (name, value) in headers {
+            let value = match value {
+                Value::String(value) => truncate(value),
+                other => truncate(&other.to_string()),
+            };
+            pretty.push_str(&format!("{}: {}\n", name, value));
+        }
+        if let Some(body) = self.body() {
+            pretty.push_str(&format!("\n{}\n", truncate(body)));
+        }
+        pretty
+    }
+
+    /// Builds the event of an `api/response` decoded without its headers
+    pub(crate) fn api_response(body: Bytes) -> Self {
+        let headers = [
+            ("Content-Type", Value::from("api/response")),
+            ("Content-Length", Value::from(body.len().to_string())),
+        ];
+        let headers = headers
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        Self::new(headers, Some(body), None)
+    }
+
+    /// Returns headers merged with the fields of a `text/event-json` body
+    pub(crate) fn fields(&self) -> HashMap<String, Value> {
+        let mut fields: HashMap<String, Value> = self.headers.clone().into_iter().collect();
+        if let Some(body) = self.body() {
+            if let Ok(json) = serde_json::from_str::<HashMap<String, Value>>(body) {
+                fields.extend(json);
+            }
+        }
+        fields
+    }
+}
+
+fn truncate(value: &str) -> String {
+    let length = value.chars().count();
+    if length <= PRETTY_VALUE_LIMIT {
+        return value.to_string();
+    }
+    let shown: String = value.chars().take(PRETTY_VALUE_LIMIT).collect();
+    format!("{}… ({} chars)", shown, length)
+}
+
+/// Strips one trailing newline, keeping any other whitespace of the body
+pub(crate) fn trim_body(body: &str) -> &str {
+    body.strip_suffix('\n').unwrap_or(body)
+}
+
+pub(crate) fn field_string(fields: &HashMap<String, Value>, key: &str) -> Option<String> {
+    fields.get(key)?.as_str().map(|value| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Event;
+
+    #[test]
+    fn pretty_string_is_sorted_and_truncated() {
+        let headers = [
+            ("Event-Name", json!("CUSTOM")),
+            ("Content-Length", json!(12)),
+            ("Core-UUID", json!("core")),
+            ("Variable-Long", json!("x".repeat(130))),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+        let event = Event::new(headers, Some("hello world!".into()), None);
+        let expected = format!(
+            "Content-Length: 12\nCore-UUID: core\nEvent-Name: CUSTOM\nVariable-Long: {}… (130 chars)\n\nhello world!\n",
+            "x".repeat(120)
+        );
+        assert_eq!(expected, event.to_pretty_string());
+    }
+
+    #[test]
+    fn body_trimmed_strips_one_newline() {
+        let event =
+            |body: &str| Event::new(Default::default(), Some(body.to_string().into()), None);
+        assert_eq!(Some("+OK up"), event("+OK up\n").body_trimmed());
+        assert_eq!(Some("+OK up"), event("+OK up").body_trimmed());
+        assert_eq!(Some("line1\n"), event("line1\n\n").body_trimmed());
+        assert_eq!(Some("line1\nline2"), event("line1\nline2\n").body_trimmed());
+    }
+
+    #[test]
+    fn body_is_converted_from_raw_body() {
+        let event = Event::new(Default::default(), Some(b"caf\xe9\n"[..].into()), None);
+        let unread = event.clone();
+        assert_eq!(&Some("caf\u{fffd}\n".to_string()), event.body());
+        assert_eq!(Some(&b"caf\xe9\n"[..]), event.body_bytes());
+        assert_eq!(unread, event);
     }
 }
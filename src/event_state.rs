@@ -0,0 +1,117 @@
+use tokio::net::TcpStream;
+
+use crate::{AuthMethod, Esl, EslConfig, EslConnection, EslError, Event, PasswordAuth};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Subscriptions, filters and `myevents` applied to a connection.
+///
+/// Freeswitch forgets them with the socket, so they are taken from the old
+/// connection with [`EslConnection::event_state`] and replayed on the new one
+/// with [`EslConnection::restore_event_state`], however it was authenticated.
+pub struct EventState {
+    /// Events of all accepted subscribes, less the unsubscribed ones
+    pub events: Vec<String>,
+    /// Header and value of each filter
+    pub filters: Vec<(String, String)>,
+    /// Uuid of the last `myevents`
+    pub myevents: Option<String>,
+}
+
+/// Returns `ApiError` for a `-ERR` reply of an event command
+fn check_reply(reply: Event) -> Result<Event, EslError> {
+    match reply
+        .reply_text()
+        .and_then(|text| text.strip_prefix("-ERR"))
+    {
+        Some(error) => Err(EslError::ApiError(error.trim().to_string())),
+        None => Ok(reply),
+    }
+}
+
+impl EslConnection {
+    /// Only passes events whose `header` has `value`, adding to earlier filters
+    pub async fn filter(&self, header: &str, value: &str) -> Result<Event, EslError> {
+        let reply = self
+            .send_recv(format!("filter {} {}", header, value).as_bytes())
+            .await?;
+        let reply = check_reply(reply)?;
+        let filter = (header.to_string(), value.to_string());
+        let mut filters = self.filters.lock().unwrap();
+        if !filters.contains(&filter) {
+            filters.push(filter);
+        }
+        Ok(reply)
+    }
+
+    /// Removes a filter added with [`EslConnection::filter`]
+    pub async fn filter_delete(&self, header: &str, value: &str) -> Result<Event, EslError> {
+        let reply = self
+            .send_recv(format!("filter delete {} {}", header, value).as_bytes())
+            .await?;
+        let reply = check_reply(reply)?;
+        self.filters
+            .lock()
+            .unwrap()
+            .retain(|(name, filtered)| name != header || filtered != value);
+        Ok(reply)
+    }
+
+    /// Receives all events of the channel `uuid` on an inbound connection
+    pub async fn myevents(&self, uuid: &str) -> Result<Event, EslError> {
+        let reply = self
+            .send_recv(format!("myevents {}", uuid).as_bytes())
+            .await?;
+        let reply = check_reply(reply)?;
+        *self.myevents_uuid.lock().unwrap() = Some(uuid.to_string());
+        Ok(reply)
+    }
+
+    /// Returns subscriptions, filters and `myevents` applied to this connection
+    pub fn event_state(&self) -> EventState {
+        EventState {
+            events: self.subscribed_events(),
+            filters: self.filters.lock().unwrap().clone(),
+            myevents: self.myevents_uuid.lock().unwrap().clone(),
+        }
+    }
+
+    /// Applies subscriptions, filters and `myevents` of `state` to this connection
+    pub async fn restore_event_state(&self, state: &EventState) -> Result<(), EslError> {
+        if !state.events.is_empty() {
+            let events: Vec<&str> = state.events.iter().map(String::as_str).collect();
+            check_reply(self.subscribe(events).await?)?;
+        }
+        for (header, value) in &state.filters {
+            self.filter(header, value).await?;
+        }
+        if let Some(uuid) = &state.myevents {
+            self.myevents(uuid).await?;
+        }
+        Ok(())
+    }
+}
+
+impl Esl {
+    /// Creates new inbound connection which continues `state` of a lost one,
+    /// so its subscribed events keep arriving
+    pub async fn reconnect(
+        stream: TcpStream,
+        password: impl ToString,
+        config: EslConfig,
+        state: &EventState,
+    ) -> Result<EslConnection, EslError> {
+        Self::reconnect_with_auth(stream, PasswordAuth::new(password), config, state).await
+    }
+
+    /// Like [`Esl::reconnect`], answering the auth request with `auth`
+    pub async fn reconnect_with_auth(
+        stream: TcpStream,
+        auth: impl AuthMethod + 'static,
+        config: EslConfig,
+        state: &EventState,
+    ) -> Result<EslConnection, EslError> {
+        let connection = Self::inbound_with_auth(stream, auth, config).await?;
+        connection.restore_event_state(state).await?;
+        Ok(connection)
+    }
+}
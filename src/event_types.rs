@@ -0,0 +1,427 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde_json::Value;
+use tokio_stream::StreamExt;
+
+use crate::event::field_string;
+use crate::{EslConnection, EslError, Event};
+
+/// Event names known to freeswitch, checked by [`crate::EslConfig::strict_events`]
+pub(crate) const EVENT_NAMES: &[&str] = &[
+    "CUSTOM",
+    "CLONE",
+    "CHANNEL_CREATE",
+    "CHANNEL_DESTROY",
+    "CHANNEL_STATE",
+    "CHANNEL_CALLSTATE",
+    "CHANNEL_ANSWER",
+    "CHANNEL_HANGUP",
+    "CHANNEL_HANGUP_COMPLETE",
+    "CHANNEL_EXECUTE",
+    "CHANNEL_EXECUTE_COMPLETE",
+    "CHANNEL_HOLD",
+    "CHANNEL_UNHOLD",
+    "CHANNEL_BRIDGE",
+    "CHANNEL_UNBRIDGE",
+    "CHANNEL_PROGRESS",
+    "CHANNEL_PROGRESS_MEDIA",
+    "CHANNEL_OUTGOING",
+    "CHANNEL_PARK",
+    "CHANNEL_UNPARK",
+    "CHANNEL_APPLICATION",
+    "CHANNEL_ORIGINATE",
+    "CHANNEL_UUID",
+    "API",
+    "LOG",
+    "INBOUND_CHAN",
+    "OUTBOUND_CHAN",
+    "STARTUP",
+    "SHUTDOWN",
+    "PUBLISH",
+    "UNPUBLISH",
+    "TALK",
+    "NOTALK",
+    "SESSION_CRASH",
+    "MODULE_LOAD",
+    "MODULE_UNLOAD",
+    "DTMF",
+    "MESSAGE",
+    "PRESENCE_IN",
+    "NOTIFY_IN",
+    "PRESENCE_OUT",
+    "PRESENCE_PROBE",
+    "MESSAGE_WAITING",
+    "MESSAGE_QUERY",
+    "ROSTER",
+    "CODEC",
+    "BACKGROUND_JOB",
+    "DETECTED_SPEECH",
+    "DETECTED_TONE",
+    "PRIVATE_COMMAND",
+    "HEARTBEAT",
+    "TRAP",
+    "ADD_SCHEDULE",
+    "DEL_SCHEDULE",
+    "EXE_SCHEDULE",
+    "RE_SCHEDULE",
+    "RELOADXML",
+    "NOTIFY",
+    "PHONE_FEATURE",
+    "PHONE_FEATURE_SUBSCRIBE",
+    "SEND_MESSAGE",
+    "RECV_MESSAGE",
+    "REQUEST_PARAMS",
+    "CHANNEL_DATA",
+    "GENERAL",
+    "COMMAND",
+    "SESSION_HEARTBEAT",
+    "CLIENT_DISCONNECTED",
+    "SERVER_DISCONNECTED",
+    "SEND_INFO",
+    "RECV_INFO",
+    "RECV_RTCP_MESSAGE",
+    "SEND_RTCP_MESSAGE",
+    "CALL_SECURE",
+    "NAT",
+    "RECORD_START",
+    "RECORD_STOP",
+    "PLAYBACK_START",
+    "PLAYBACK_STOP",
+    "CALL_UPDATE",
+    "FAILURE",
+    "SOCKET_DATA",
+    "MEDIA_BUG_START",
+    "MEDIA_BUG_STOP",
+    "CONFERENCE_DATA_QUERY",
+    "CONFERENCE_DATA",
+    "CALL_SETUP_REQ",
+    "CALL_SETUP_RESULT",
+    "CALL_DETAIL",
+    "DEVICE_STATE",
+    "TEXT",
+    "SHUTDOWN_REQUESTED",
+    "ALL",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Event requested by [`EslConnection::subscribe`]
+pub enum EventSubscription {
+    /// Standard event like `CHANNEL_HANGUP`
+    Event(String),
+    /// `CUSTOM` event of a subclass like `sofia::register`
+    Custom(String),
+}
+
+impl From<&str> for EventSubscription {
+    fn from(name: &str) -> Self {
+        Self::Event(name.to_string())
+    }
+}
+
+impl From<String> for EventSubscription {
+    fn from(name: String) -> Self {
+        Self::Event(name)
+    }
+}
+
+/// Returns arguments of the `event` command, with all subclasses after one `CUSTOM`
+/// as freeswitch reads every name following it as a subclass
+pub(crate) fn subscription_names(events: &[EventSubscription]) -> Vec<String> {
+    let mut names = Vec::with_capacity(events.len() + 1);
+    let mut subclasses = Vec::new();
+    for event in events {
+        match event {
+            EventSubscription::Event(name) => names.push(name.clone()),
+            EventSubscription::Custom(subclass) => subclasses.push(subclass.clone()),
+        }
+    }
+    if !subclasses.is_empty() {
+        names.push("CUSTOM".to_string());
+        names.extend(subclasses);
+    }
+    names
+}
+
+/// Returns first name which isn't a known event. Names after `CUSTOM` are
+/// subclasses like `sofia::register` and aren't checked.
+pub(crate) fn unknown_event<'a>(names: &[&'a str]) -> Option<&'a str> {
+    names
+        .iter()
+        .take_while(|name| **name != "CUSTOM")
+        .find(|name| !EVENT_NAMES.contains(name))
+        .copied()
+}
+
+fn expect_event(event: &Event, names: &[&str]) -> Result<HashMap<String, Value>, EslError> {
+    let fields = event.fields();
+    let name = field_string(&fields, "Event-Name").unwrap_or_default();
+    if !names.contains(&name.as_str()) {
+        return Err(EslError::UnexpectedEvent(name));
+    }
+    Ok(fields)
+}
+
+fn expect_custom(event: &Event, subclasses: &[&str]) -> Result<HashMap<String, Value>, EslError> {
+    let fields = expect_event(event, &["CUSTOM"])?;
+    let subclass = field_string(&fields, "Event-Subclass").unwrap_or_default();
+    if !subclasses.contains(&subclass.as_str()) {
+        return Err(EslError::UnexpectedEvent(subclass));
+    }
+    Ok(fields)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `CHANNEL_PROGRESS` or `CHANNEL_PROGRESS_MEDIA` event of a ringing call
+pub struct ProgressEvent {
+    /// Uuid of the channel
+    pub uuid: Option<String>,
+    /// True for `CHANNEL_PROGRESS_MEDIA`, i.e. early media instead of plain ringing
+    pub media: bool,
+    /// Value of `Answer-State` e.g. `ringing` or `early`
+    pub answer_state: Option<String>,
+}
+
+impl TryFrom<&Event> for ProgressEvent {
+    type Error = EslError;
+    fn try_from(event: &Event) -> Result<Self, Self::Error> {
+        let fields = expect_event(event, &["CHANNEL_PROGRESS", "CHANNEL_PROGRESS_MEDIA"])?;
+        Ok(Self {
+            uuid: field_string(&fields, "Unique-ID"),
+            media: field_string(&fields, "Event-Name").as_deref() == Some("CHANNEL_PROGRESS_MEDIA"),
+            answer_state: field_string(&fields, "Answer-State"),
+        })
+    }
+}
+
+impl EslConnection {
+    /// Waits for the next progress event of the call.
+    ///
+    /// On outbound connections only events of the controlled call are considered,
+    /// on inbound connections the events must be subscribed to first.
+    /// Events received before this is called are not seen.
+    /// Returns `None` if no progress event arrives within `timeout`.
+    pub async fn wait_for_progress(
+        &self,
+        timeout: Duration,
+    ) -> Result<Option<ProgressEvent>, EslError> {
+        let events = self.events_filtered(&["CHANNEL_PROGRESS", "CHANNEL_PROGRESS_MEDIA"]);
+        let mut events = std::pin::pin!(events);
+        let wait = async {
+            while let Some(event) = events.next().await {
+                let progress = ProgressEvent::try_from(&event)?;
+                if self.call_uuid.is_none() || progress.uuid == self.call_uuid {
+                    return Ok(Some(progress));
+                }
+            }
+            Err(EslError::Disconnected)
+        };
+        match tokio::time::timeout(timeout, wait).await {
+            Ok(result) => result,
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `PLAYBACK_START` or `PLAYBACK_STOP` event of a file played on a channel.
+///
+/// The events must be subscribed to, e.g. with
+/// `subscribe(vec!["PLAYBACK_START", "PLAYBACK_STOP"])`.
+pub struct PlaybackEvent {
+    /// Uuid of the channel
+    pub uuid: Option<String>,
+    /// Value of `Playback-File-Path`
+    pub file: Option<String>,
+    /// True for `PLAYBACK_STOP`
+    pub stopped: bool,
+    /// Value of `Playback-Status` of a stop, `done` or `break` when interrupted
+    pub status: Option<String>,
+    /// Samples played when stopped, to resume with `playback` at that position
+    pub samples: Option<u64>,
+    /// Milliseconds played when stopped
+    pub ms: Option<u64>,
+}
+
+impl TryFrom<&Event> for PlaybackEvent {
+    type Error = EslError;
+    fn try_from(event: &Event) -> Result<Self, Self::Error> {
+        let fields = expect_event(event, &["PLAYBACK_START", "PLAYBACK_STOP"])?;
+        let stopped = field_string(&fields, "Event-Name").as_deref() == Some("PLAYBACK_STOP");
+        // the variables of a start event still belong to the previous playback
+        let number = |key: &str| field_string(&fields, key)?.parse().ok().filter(|_| stopped);
+        Ok(Self {
+            uuid: field_string(&fields, "Unique-ID"),
+            file: field_string(&fields, "Playback-File-Path"),
+            stopped,
+            status: field_string(&fields, "Playback-Status"),
+            samples: number("variable_playback_samples"),
+            ms: number("variable_playback_ms"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Member of a conference as reported by its events
+pub struct ConferenceMember {
+    /// Name of the conference
+    pub conference: Option<String>,
+    /// Id of the member within the conference
+    pub member_id: Option<String>,
+    /// Uuid of the member's channel
+    pub uuid: Option<String>,
+    /// Caller id number of the member
+    pub caller_id_number: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+/// Member change from a `conference::maintenance` CUSTOM event
+pub enum ConferenceEvent {
+    /// `add-member`
+    MemberJoin(ConferenceMember),
+    /// `del-member`
+    MemberLeave(ConferenceMember),
+    /// `start-talking`
+    StartTalking(ConferenceMember),
+    /// `stop-talking`
+    StopTalking(ConferenceMember),
+    /// `mute-member`
+    Mute(ConferenceMember),
+    /// `unmute-member`
+    Unmute(ConferenceMember),
+}
+
+impl TryFrom<&Event> for ConferenceEvent {
+    type Error = EslError;
+    fn try_from(event: &Event) -> Result<Self, Self::Error> {
+        let fields = expect_custom(event, &["conference::maintenance"])?;
+        let member = ConferenceMember {
+            conference: field_string(&fields, "Conference-Name"),
+            member_id: field_string(&fields, "Member-ID"),
+            uuid: field_string(&fields, "Unique-ID"),
+            caller_id_number: field_string(&fields, "Caller-Caller-ID-Number"),
+        };
+        let action = field_string(&fields, "Action").unwrap_or_default();
+        match action.as_str() {
+            "add-member" => Ok(Self::MemberJoin(member)),
+            "del-member" => Ok(Self::MemberLeave(member)),
+            "start-talking" => Ok(Self::StartTalking(member)),
+            "stop-talking" => Ok(Self::StopTalking(member)),
+            "mute-member" => Ok(Self::Mute(member)),
+            "unmute-member" => Ok(Self::Unmute(member)),
+            _ => Err(EslError::UnexpectedEvent(format!(
+                "conference::maintenance {}",
+                action
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Kind of a sofia registration event
+pub enum RegistrationAction {
+    /// `sofia::register`
+    Register,
+    /// `sofia::unregister`
+    Unregister,
+    /// `sofia::expire`
+    Expire,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `sofia::register`, `sofia::unregister` or `sofia::expire` CUSTOM event
+pub struct RegistrationEvent {
+    /// Registered user
+    pub user: Option<String>,
+    /// Realm or host of the user
+    pub realm: Option<String>,
+    /// Contact uri of the registration
+    pub contact: Option<String>,
+    /// Address the registration came from, not sent with `sofia::expire`
+    pub network_ip: Option<String>,
+    /// Kind of the event
+    pub action: RegistrationAction,
+}
+
+impl TryFrom<&Event> for RegistrationEvent {
+    type Error = EslError;
+    fn try_from(event: &Event) -> Result<Self, Self::Error> {
+        let fields = expect_custom(
+            event,
+            &["sofia::register", "sofia::unregister", "sofia::expire"],
+        )?;
+        let action = match field_string(&fields, "Event-Subclass").as_deref() {
+            Some("sofia::register") => RegistrationAction::Register,
+            Some("sofia::unregister") => RegistrationAction::Unregister,
+            _ => RegistrationAction::Expire,
+        };
+        // expire events name the fields `user` and `host`
+        let first = |keys: &[&str]| keys.iter().find_map(|key| field_string(&fields, key));
+        Ok(Self {
+            user: first(&["from-user", "username", "user"]),
+            realm: first(&["realm", "from-host", "host"]),
+            contact: field_string(&fields, "contact"),
+            network_ip: field_string(&fields, "network-ip"),
+            action,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `CHANNEL_CREATE` or `CHANNEL_DESTROY` event, to track the legs of calls
+pub struct ChannelLifecycleEvent {
+    /// Uuid of the channel
+    pub uuid: Option<String>,
+    /// True for `CHANNEL_DESTROY`
+    pub destroyed: bool,
+    /// Value of `Call-Direction`, `inbound` or `outbound`
+    pub direction: Option<String>,
+    /// Value of `Channel-Name` e.g. `sofia/internal/1000@example.com`
+    pub channel_name: Option<String>,
+    /// Uuid of the other leg, set on B-legs created by a bridge or originate
+    pub other_leg_uuid: Option<String>,
+}
+
+impl TryFrom<&Event> for ChannelLifecycleEvent {
+    type Error = EslError;
+    fn try_from(event: &Event) -> Result<Self, Self::Error> {
+        let fields = expect_event(event, &["CHANNEL_CREATE", "CHANNEL_DESTROY"])?;
+        Ok(Self {
+            uuid: field_string(&fields, "Unique-ID"),
+            destroyed: field_string(&fields, "Event-Name").as_deref() == Some("CHANNEL_DESTROY"),
+            direction: field_string(&fields, "Call-Direction"),
+            channel_name: field_string(&fields, "Channel-Name"),
+            other_leg_uuid: field_string(&fields, "Other-Leg-Unique-ID"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `CHANNEL_BRIDGE` or `CHANNEL_UNBRIDGE` event joining two legs
+pub struct BridgeEvent {
+    /// Value of `Bridge-A-Unique-ID`, the leg which ran the bridge
+    pub a_leg_uuid: Option<String>,
+    /// Value of `Bridge-B-Unique-ID`, the leg it was bridged to
+    pub b_leg_uuid: Option<String>,
+    /// True for `CHANNEL_UNBRIDGE`
+    pub unbridged: bool,
+}
+
+impl TryFrom<&Event> for BridgeEvent {
+    type Error = EslError;
+    fn try_from(event: &Event) -> Result<Self, Self::Error> {
+        let fields = expect_event(event, &["CHANNEL_BRIDGE", "CHANNEL_UNBRIDGE"])?;
+        Ok(Self::from_fields(&fields))
+    }
+}
+
+impl BridgeEvent {
+    pub(crate) fn from_fields(fields: &HashMap<String, Value>) -> Self {
+        Self {
+            a_leg_uuid: field_string(fields, "Bridge-A-Unique-ID"),
+            b_leg_uuid: field_string(fields, "Bridge-B-Unique-ID"),
+            unbridged: field_string(fields, "Event-Name").as_deref() == Some("CHANNEL_UNBRIDGE"),
+        }
+    }
+}
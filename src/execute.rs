@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+use tokio::sync::oneshot::Receiver;
+
+use crate::connection::{execute_completion, JobGuard};
+use crate::event::field_string;
+use crate::{EslConnection, EslError, Event};
+
+#[derive(Debug)]
+/// Application started by [`EslConnection::execute_detached`].
+///
+/// Dropping it without waiting stops tracking the completion, which then goes
+/// to [`EslConnection::events`].
+pub struct DetachedExecution<'a> {
+    app_name: String,
+    rx: Receiver<Event>,
+    guard: JobGuard<'a>,
+}
+
+impl DetachedExecution<'_> {
+    /// Returns `Event-UUID` of the execute, which its completion event carries
+    pub fn event_uuid(&self) -> &str {
+        self.guard.uuid()
+    }
+
+    /// Waits until the application finished, returning its `CHANNEL_EXECUTE_COMPLETE` event.
+    ///
+    /// A completion which arrived earlier is kept.
+    pub async fn wait(self) -> Result<Event, EslError> {
+        // the guard is dropped after the completion, or with a wait which is given up
+        execute_completion(&self.app_name, self.rx).await
+    }
+}
+
+/// Energy level below which `record` counts audio as silence
+const RECORD_SILENCE_THRESHOLD: u32 = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// `CHANNEL_EXECUTE_COMPLETE` event of an application together with its typed view
+pub struct ExecuteResult {
+    app_name: String,
+    fields: HashMap<String, Value>,
+    event: Event,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+/// Typed view of an application response, chosen by application name
+pub enum AppResponse {
+    /// Response of `playback`
+    Playback(PlaybackResult),
+    /// Response of `bridge`
+    Bridge(BridgeResult),
+    /// Response of `record`
+    Record(RecordResult),
+    /// Response of an application without a typed view
+    Other(Option<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Result of the `playback` application
+pub struct PlaybackResult {
+    /// Value of `Application-Response` e.g. `FILE PLAYED`
+    pub response: Option<String>,
+    /// DTMF digit which stopped the playback
+    pub terminator: Option<String>,
+    /// Milliseconds of the file that were played
+    pub played_ms: Option<u64>,
+    /// Samples of the file that were played
+    pub samples: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Result of the `record` application
+pub struct RecordResult {
+    /// Value of `Application-Response`
+    pub response: Option<String>,
+    /// DTMF digit which stopped the recording, `None` when it hit the time or silence limit
+    pub terminator: Option<String>,
+    /// Milliseconds that were recorded
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Result of the `bridge` application
+pub struct BridgeResult {
+    /// Value of `Application-Response`
+    pub response: Option<String>,
+    /// Value of `DIALSTATUS` e.g. `SUCCESS` or `BUSY`
+    pub dial_status: Option<String>,
+    /// Value of `originate_disposition` e.g. `USER_BUSY`
+    pub disposition: Option<String>,
+    /// Uuid of the B-leg which was bridged
+    pub other_leg_uuid: Option<String>,
+    /// Hangup cause of the B-leg
+    pub hangup_cause: Option<String>,
+}
+
+impl BridgeResult {
+    /// Returns true when the B-leg was bridged
+    pub fn succeeded(&self) -> bool {
+        self.dial_status.as_deref() == Some("SUCCESS")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+/// Media state of a channel, e.g. after `answer` or `pre_answer`
+pub enum MediaState {
+    /// Early media is flowing but the call isn't answered, call state `EARLY`
+    Early,
+    /// The call is answered, call state `ACTIVE`
+    Active,
+    /// Any other call state, e.g. `HANGUP` when the call ended meanwhile
+    Other(String),
+}
+
+impl From<Event> for ExecuteResult {
+    fn from(event: Event) -> Self {
+        let fields = event.fields();
+        let app_name = field_string(&fields, "Application").unwrap_or_default();
+        Self {
+            app_name,
+            fields,
+            event,
+        }
+    }
+}
+
+impl ExecuteResult {
+    /// Returns name of the executed application
+    pub fn app_name(&self) -> &str {
+        &self.app_name
+    }
+
+    /// Returns `Application-Response` of the executed application
+    pub fn response(&self) -> Option<String> {
+        self.get("Application-Response")
+    }
+
+    /// Returns field of the completion event as string
+    pub fn get(&self, key: &str) -> Option<String> {
+        field_string(&self.fields, key)
+    }
+
+    /// Returns the raw completion event
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+
+    /// Returns typed view of the response based on the application name
+    pub fn view(&self) -> AppResponse {
+        match self.app_name.as_str() {
+            "playback" => AppResponse::Playback(PlaybackResult {
+                response: self.response(),
+                terminator: self.get("variable_playback_terminator_used"),
+                played_ms: self.get_number("variable_playback_ms"),
+                samples: self.get_number("variable_playback_samples"),
+            }),
+            "bridge" => AppResponse::Bridge(BridgeResult {
+                response: self.response(),
+                dial_status: self.get("variable_DIALSTATUS"),
+                disposition: self.get("variable_originate_disposition"),
+                other_leg_uuid: self.get("variable_last_bridge_to"),
+                hangup_cause: self.get("variable_last_bridge_hangup_cause"),
+            }),
+            "record" => AppResponse::Record(self.record()),
+            _ => AppResponse::Other(self.response()),
+        }
+    }
+
+    fn record(&self) -> RecordResult {
+        RecordResult {
+            response: self.response(),
+            terminator: self.get("variable_playback_terminator_used"),
+            duration_ms: self.get_number("variable_record_ms"),
+        }
+    }
+
+    /// Returns media state of the channel when the application completed
+    pub fn media_state(&self) -> Option<MediaState> {
+        if let Some(state) = self.get("Channel-Call-State") {
+            return Some(match state.as_str() {
+                "EARLY" => MediaState::Early,
+                "ACTIVE" => MediaState::Active,
+                _ => MediaState::Other(state),
+            });
+        }
+        match self.get("Answer-State")?.as_str() {
+            "early" => Some(MediaState::Early),
+            "answered" => Some(MediaState::Active),
+            other => Some(MediaState::Other(other.to_string())),
+        }
+    }
+
+    fn get_number(&self, key: &str) -> Option<u64> {
+        self.get(key)?.parse().ok()
+    }
+}
+
+impl EslConnection {
+    /// executes application in freeswitch and returns its typed result
+    pub async fn execute_result(
+        &self,
+        app_name: &str,
+        app_args: &str,
+    ) -> Result<ExecuteResult, EslError> {
+        let event = self.execute(app_name, app_args).await?;
+        Ok(ExecuteResult::from(event))
+    }
+
+    /// executes application which stores its result in the channel variable `variable`,
+    /// like `read`, returning the completion event with the value of the variable.
+    ///
+    /// The value is taken from the completion event, so no extra command is needed.
+    pub async fn execute_with_variable(
+        &self,
+        app_name: &str,
+        app_args: &str,
+        variable: &str,
+    ) -> Result<(Event, Option<String>), EslError> {
+        let result = self.execute_result(app_name, app_args).await?;
+        let value = result.get(&format!("variable_{}", variable));
+        Ok((result.event, value))
+    }
+
+    /// executes application with `async: true` without waiting for it to finish,
+    /// returning a handle to wait for its completion.
+    ///
+    /// Meanwhile other commands can be sent, e.g. to break a long playback.
+    pub async fn execute_detached(
+        &self,
+        app_name: &str,
+        app_args: &str,
+    ) -> Result<DetachedExecution<'_>, EslError> {
+        let (event_uuid, rx) = self
+            .start_execute(app_name, app_args, &[("async", "true")])
+            .await?;
+        Ok(DetachedExecution {
+            app_name: app_name.to_string(),
+            rx,
+            guard: self.job_guard(event_uuid),
+        })
+    }
+
+    /// answers call in outbound mode, returning whether it ended up answered
+    pub async fn answer_with_state(&self) -> Result<MediaState, EslError> {
+        self.media_state_after("answer").await
+    }
+
+    /// sends early media in outbound mode without answering, returning the resulting state
+    pub async fn pre_answer_with_state(&self) -> Result<MediaState, EslError> {
+        self.media_state_after("pre_answer").await
+    }
+
+    /// plays `beep_file`, then records the caller to `path` in outbound mode.
+    ///
+    /// The recording stops after `max_secs`, after `silence_secs` of silence or
+    /// when one of `terminators` is pressed, which stay set for the session.
+    pub async fn record_with_beep(
+        &self,
+        beep_file: &str,
+        path: &str,
+        max_secs: u32,
+        silence_secs: u32,
+        terminators: &str,
+    ) -> Result<RecordResult, EslError> {
+        self.playback(beep_file).await?;
+        self.set_playback_terminators(terminators).await?;
+        let args = format!(
+            "{} {} {} {}",
+            path, max_secs, RECORD_SILENCE_THRESHOLD, silence_secs
+        );
+        let result = self.execute_result("record", &args).await?;
+        Ok(result.record())
+    }
+
+    async fn media_state_after(&self, app_name: &str) -> Result<MediaState, EslError> {
+        let result = self.execute_result(app_name, "").await?;
+        result.media_state().ok_or_else(|| {
+            EslError::InternalError(format!("{} completed without call state", app_name))
+        })
+    }
+}
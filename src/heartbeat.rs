@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::warn;
+
+#[derive(Debug, Clone, Default)]
+/// Time of the last `HEARTBEAT` while they are subscribed, filled by the reader task
+pub(crate) struct HeartbeatWatch(Arc<Mutex<Option<Instant>>>);
+
+impl HeartbeatWatch {
+    /// Records a `HEARTBEAT` event, ignoring other events
+    pub(crate) fn record(&self, fields: &HashMap<String, Value>) {
+        if fields.get("Event-Name").and_then(Value::as_str) != Some("HEARTBEAT") {
+            return;
+        }
+        let mut last = self.0.lock().unwrap();
+        if last.is_some() {
+            *last = Some(Instant::now());
+        }
+    }
+
+    /// Starts or stops expecting heartbeats after a subscription changed
+    pub(crate) fn expect(&self, names: &[String]) {
+        let expected = names
+            .iter()
+            .any(|name| name == "HEARTBEAT" || name == "ALL");
+        *self.0.lock().unwrap() = expected.then(Instant::now);
+    }
+
+    /// Marks the connection as not connected once heartbeats are expected but
+    /// none arrived for twice `interval`. Stops when the returned guard is dropped,
+    /// which the connection holds.
+    pub(crate) fn spawn_watchdog(
+        &self,
+        interval: Duration,
+        connected: Arc<AtomicBool>,
+    ) -> DropGuard {
+        let watch = self.clone();
+        let stop = CancellationToken::new();
+        let stopped = stop.clone();
+        tokio::spawn(async move {
+            loop {
+                let sleep = tokio::time::sleep(interval / 2);
+                if stopped.run_until_cancelled(sleep).await.is_none() {
+                    return;
+                }
+                let last = *watch.0.lock().unwrap();
+                if last.is_some_and(|last| last.elapsed() > interval * 2) {
+                    warn!("no HEARTBEAT for {:?}, connection seems dead", interval * 2);
+                    connected.store(false, Ordering::Relaxed);
+                    return;
+                }
+            }
+        });
+        stop.drop_guard()
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+use crate::event::field_string;
+use crate::{EslConnection, EslError};
+
+/// Reply of `uuid_hold off` for a channel which isn't on hold
+const NOT_HELD: &str = "Operation failed";
+
+#[derive(Debug, Clone, Default)]
+/// Known hold state of channels, filled by the reader task from `CHANNEL_HOLD` and
+/// `CHANNEL_UNHOLD`. Channels without an entry may be held by anyone.
+pub(crate) struct HoldState(Arc<Mutex<HashMap<String, bool>>>);
+
+impl HoldState {
+    /// Updates the hold state of the channel of a hold event, ignoring other events
+    pub(crate) fn record(&self, fields: &HashMap<String, Value>) {
+        let Some(uuid) = field_string(fields, "Unique-ID") else {
+            return;
+        };
+        match fields.get("Event-Name").and_then(Value::as_str) {
+            Some("CHANNEL_HOLD") => self.set(&uuid, true),
+            Some("CHANNEL_UNHOLD") => self.set(&uuid, false),
+            Some("CHANNEL_DESTROY") => {
+                self.0.lock().unwrap().remove(&uuid);
+            }
+            _ => {}
+        }
+    }
+
+    fn set(&self, uuid: &str, held: bool) {
+        self.0.lock().unwrap().insert(uuid.to_string(), held);
+    }
+
+    /// Returns whether the channel is held, `None` when this connection never saw it
+    fn get(&self, uuid: &str) -> Option<bool> {
+        self.0.lock().unwrap().get(uuid).copied()
+    }
+}
+
+impl EslConnection {
+    /// Puts a channel on hold with `uuid_hold`, doing nothing when it already is.
+    ///
+    /// Holds made by others are only known from `CHANNEL_HOLD` events, which
+    /// outbound connections receive for their call and inbound ones after subscribing.
+    pub async fn hold(&self, uuid: &str) -> Result<(), EslError> {
+        if self.holds.get(uuid) == Some(true) {
+            return Ok(());
+        }
+        self.uuid_api(&format!("uuid_hold {}", uuid)).await?;
+        self.holds.set(uuid, true);
+        Ok(())
+    }
+
+    /// Takes a channel off hold with `uuid_hold off`, doing nothing when it is known not to be held.
+    ///
+    /// A channel this connection saw no hold event of is sent `uuid_hold off` anyway,
+    /// as it may have been held by another client, and freeswitch refusing because
+    /// it isn't on hold counts as success.
+    pub async fn unhold(&self, uuid: &str) -> Result<(), EslError> {
+        if self.holds.get(uuid) == Some(false) {
+            return Ok(());
+        }
+        match self.uuid_api(&format!("uuid_hold off {}", uuid)).await {
+            Ok(_) => {}
+            Err(EslError::ApiError(reason)) if reason.eq_ignore_ascii_case(NOT_HELD) => {}
+            Err(e) => return Err(e),
+        }
+        self.holds.set(uuid, false);
+        Ok(())
+    }
+
+    /// Returns whether a channel is on hold, as far as this connection knows
+    pub fn is_held(&self, uuid: &str) -> bool {
+        self.holds.get(uuid) == Some(true)
+    }
+}
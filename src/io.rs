@@ -1,24 +1,122 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
 
-use bytes::Buf;
+use bytes::{Buf, Bytes};
+use indexmap::IndexMap;
 use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
 use tokio_util::codec::{Decoder, Encoder};
 use tracing::{trace, warn};
 
 use crate::{event::Event, EslError};
 
-#[derive(Debug, Clone)]
-pub(crate) struct EslCodec {}
+#[derive(Debug, Clone, Default)]
+pub(crate) struct EslCodec {
+    /// receives copies of all raw bytes when io tracing is enabled
+    tap: Option<UnboundedSender<Bytes>>,
+    /// keeps the bytes of each frame in [`Event::raw_frame`]
+    retain_raw: bool,
+    /// skipping bytes after a corrupt frame until the start of the next one
+    resyncing: bool,
+}
+
+impl EslCodec {
+    pub(crate) fn new(tap: Option<UnboundedSender<Bytes>>, retain_raw: bool) -> Self {
+        Self {
+            tap,
+            retain_raw,
+            resyncing: false,
+        }
+    }
+
+    fn raw_frame(&self, frame: &[u8]) -> Option<Bytes> {
+        self.retain_raw.then(|| Bytes::copy_from_slice(frame))
+    }
+
+    fn trace_io(&self, bytes: &[u8]) {
+        if let Some(tap) = &self.tap {
+            let _ = tap.send(Bytes::copy_from_slice(bytes));
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum InboundResponse {
+    /// Any frame other than a disconnect notice, including frames without `Content-Type`
+    Reply(Event),
+    /// Body of an `api/response`, decoded without building its headers
+    Api(Bytes),
+    /// `text/disconnect-notice` sent before freeswitch closes the socket
+    Disconnect(Event),
+    /// `text/event-plain` event, with the headers of its body
+    Event(Event),
+    /// Headers of a frame whose body couldn't be found, skipped to resynchronize
+    Corrupt(Event),
+}
+
+impl InboundResponse {
+    fn new(event: Event) -> Self {
+        let content_type = event.headers.get("Content-Type").and_then(|c| c.as_str());
+        match content_type {
+            Some("text/disconnect-notice") => Self::Disconnect(event),
+            Some("text/event-plain") if event.body_bytes().is_some_and(|b| !b.is_empty()) => {
+                match plain_event(&event) {
+                    Ok(event) => Self::Event(event),
+                    Err(e) => {
+                        warn!("Unable to parse plain event: {}", e);
+                        Self::Reply(event)
+                    }
+                }
+            }
+            _ => Self::Reply(event),
+        }
+    }
+
+    /// Returns `InternalError` for a corrupt frame, otherwise the response itself
+    pub(crate) fn expected(self) -> Result<Self, EslError> {
+        match self {
+            Self::Corrupt(_) => Err(EslError::InternalError(
+                "reply from freeswitch was corrupt".into(),
+            )),
+            other => Ok(other),
+        }
+    }
+
+    pub(crate) fn into_event(self) -> Event {
+        match self {
+            Self::Reply(event)
+            | Self::Disconnect(event)
+            | Self::Event(event)
+            | Self::Corrupt(event) => event,
+            Self::Api(body) => Event::api_response(body),
+        }
+    }
+}
 
 impl Encoder<&[u8]> for EslCodec {
     type Error = EslError;
     fn encode(&mut self, item: &[u8], dst: &mut bytes::BytesMut) -> Result<(), Self::Error> {
+        let start = dst.len();
         dst.extend_from_slice(item);
         dst.extend_from_slice(b"\n\n");
+        self.trace_io(&dst[start..]);
         Ok(())
     }
 }
 
+/// Headers of every `api/response` freeswitch sends, up to the length value
+const API_RESPONSE_HEADERS: &[u8] = b"Content-Type: api/response\nContent-Length: ";
+
+/// Returns body start and length of a complete `api/response` header block
+fn api_response_frame(src: &[u8]) -> Option<(usize, usize)> {
+    let rest = src.strip_prefix(API_RESPONSE_HEADERS)?;
+    let digits = rest.iter().position(|byte| *byte == b'\n')?;
+    if rest.get(digits + 1) != Some(&b'\n') {
+        return None;
+    }
+    let length = std::str::from_utf8(&rest[..digits]).ok()?.parse().ok()?;
+    Some((API_RESPONSE_HEADERS.len() + digits + 2, length))
+}
+
 fn get_header_end(src: &bytes::BytesMut) -> Option<usize> {
     trace!("get_header_end:=>{:?}", src);
     // get first new line character
@@ -29,23 +127,30 @@ fn get_header_end(src: &bytes::BytesMut) -> Option<usize> {
     }
     None
 }
-fn parse_body(src: &[u8], length: usize) -> String {
+fn parse_body(src: &[u8], length: usize) -> Bytes {
     trace!("parse body src : {}", String::from_utf8_lossy(src));
     trace!("length src : {}", length);
-    String::from_utf8_lossy(&src[..length]).to_string()
+    Bytes::copy_from_slice(&src[..length])
 }
-fn parse_header(src: &[u8]) -> Result<HashMap<String, Value>, std::io::Error> {
+/// Parses the `Name: value` lines of a frame header
+pub fn parse_header(src: &[u8]) -> Result<IndexMap<String, Value>, std::io::Error> {
     trace!("parsing this header {:#?}", String::from_utf8_lossy(src));
-    let data = String::from_utf8_lossy(src).to_string();
-    let a = data.split('\n');
-    let mut hash = HashMap::new();
-    for line in a {
-        let parts: Vec<&str> = line.split(':').collect();
-        if parts.len() == 2 {
-            // SAFETY: Index access is safe beacue we have checked the length
-            let key = parts[0].trim();
-            let val = parts[1].trim();
-            hash.insert(key.to_string(), serde_json::json!(val.to_string()));
+    // borrowed unless bytes have to be replaced
+    let data = match std::str::from_utf8(src) {
+        Ok(data) => Cow::Borrowed(data),
+        Err(e) => {
+            // keep parsing, but make the replaced bytes visible to the user
+            warn!("Invalid UTF-8 in header, replacing malformed bytes: {}", e);
+            String::from_utf8_lossy(src)
+        }
+    };
+    let lines = src.iter().filter(|byte| **byte == b'\n').count() + 1;
+    let mut hash = IndexMap::with_capacity(lines);
+    for line in data.split('\n') {
+        // reply headers aren't url encoded, so values such as `+OK Job-UUID: <uuid>`
+        // of a bgapi reply keep their own colons after the first one
+        if let Some((key, val)) = line.split_once(':') {
+            hash.insert(key.trim().to_owned(), Value::String(val.trim().to_owned()));
         } else {
             warn!("Invalid formatting while parsing header");
         }
@@ -54,11 +159,116 @@ fn parse_header(src: &[u8]) -> Result<HashMap<String, Value>, std::io::Error> {
     Ok(hash)
 }
 
-impl Decoder for EslCodec {
-    type Item = Event;
-    type Error = EslError;
-    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        trace!("decode");
+/// Builds the event carried in the body of a `text/event-plain` frame,
+/// whose url encoded headers may be followed by a body of their own
+fn plain_event(frame: &Event) -> Result<Event, std::io::Error> {
+    let raw = frame.raw_body.clone().unwrap_or_default();
+    let header_end = raw
+        .windows(2)
+        .position(|pair| pair == b"\n\n")
+        .unwrap_or(raw.len());
+    let mut headers = parse_header(raw[..header_end].trim_ascii_end())?;
+    for value in headers.values_mut() {
+        if let Some(text) = value.as_str() {
+            *value = Value::from(url_decode(text));
+        }
+    }
+    let raw_body = raw.slice((header_end + 2).min(raw.len())..);
+    Ok(Event::new(
+        headers,
+        (!raw_body.is_empty()).then_some(raw_body),
+        frame.raw_frame.clone(),
+    ))
+}
+
+/// Decodes the `%XX` escapes freeswitch uses in plain event headers
+pub(crate) fn url_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Header names a frame of freeswitch starts with
+const FRAME_STARTS: &[&[u8]] = &[b"Content-Type:", b"Content-Length:"];
+
+/// Returns position of the first line which looks like the start of a frame
+fn frame_start(src: &[u8]) -> Option<usize> {
+    let line_starts = std::iter::once(0).chain(
+        src.iter()
+            .enumerate()
+            .filter(|(_, byte)| **byte == b'\n')
+            .map(|(index, _)| index + 1),
+    );
+    line_starts
+        .filter(|start| *start < src.len())
+        .find(|start| {
+            FRAME_STARTS
+                .iter()
+                .any(|name| src[*start..].starts_with(name))
+        })
+}
+
+impl EslCodec {
+    /// Drops bytes up to the next frame, returning whether it was found
+    fn resync(&mut self, src: &mut bytes::BytesMut) -> bool {
+        let skipped = match frame_start(src) {
+            Some(start) => {
+                self.resyncing = false;
+                start
+            }
+            // the last line may be the beginning of a frame which is still arriving
+            None => src
+                .iter()
+                .rposition(|byte| *byte == b'\n')
+                .map_or(0, |n| n + 1),
+        };
+        if skipped > 0 {
+            warn!("Skipped {} bytes to resynchronize with freeswitch", skipped);
+            self.trace_io(&src[..skipped]);
+            src.advance(skipped);
+        }
+        !self.resyncing
+    }
+
+    fn decode_frame(
+        &mut self,
+        src: &mut bytes::BytesMut,
+    ) -> Result<Option<InboundResponse>, EslError> {
+        // the raw frame includes the headers, which the api fast path skips
+        if let Some((body_start, length)) = api_response_frame(src).filter(|_| !self.retain_raw) {
+            if src.len() < body_start + length {
+                return Ok(None);
+            }
+            self.trace_io(&src[..body_start + length]);
+            src.advance(body_start);
+            return Ok(Some(InboundResponse::Api(src.split_to(length).freeze())));
+        }
+        self.decode_headers(src)
+    }
+
+    /// Decodes the frame from its parsed headers, as any frame but fast path api responses
+    fn decode_headers(
+        &mut self,
+        src: &mut bytes::BytesMut,
+    ) -> Result<Option<InboundResponse>, EslError> {
         let header_end = get_header_end(src);
         let header_end = match header_end {
             Some(he) => he,
@@ -68,24 +278,300 @@ impl Decoder for EslCodec {
         trace!("parsed headers are : {:?}", headers);
         let body_start = header_end + 1;
         let Some(length) = headers.get("Content-Length") else {
+            self.trace_io(&src[..body_start]);
+            let raw_frame = self.raw_frame(&src[..body_start]);
             src.advance(body_start);
-            return Ok(Some(Event {
-                headers,
-                body: None,
-            }));
+            return Ok(Some(InboundResponse::new(Event::new(headers, None, raw_frame))));
         };
 
         let length = length.as_str().unwrap();
-        let body_length = length.parse()?;
+        let Ok(body_length) = length.parse::<usize>() else {
+            // the body can't be found, so skip it by looking for the next frame
+            warn!("Invalid Content-Length {:?}, skipping frame", length);
+            self.trace_io(&src[..body_start]);
+            let raw_frame = self.raw_frame(&src[..body_start]);
+            src.advance(body_start);
+            self.resyncing = true;
+            return Ok(Some(InboundResponse::Corrupt(Event::new(headers, None, raw_frame))));
+        };
+        if body_length == 0 {
+            self.trace_io(&src[..body_start]);
+            let raw_frame = self.raw_frame(&src[..body_start]);
+            src.advance(body_start);
+            let event = Event::new(headers, Some(Bytes::new()), raw_frame);
+            return Ok(Some(InboundResponse::new(event)));
+        }
         if src.len() < (header_end + body_length + 1) {
             trace!("returned because size was not enough");
             return Ok(None);
         }
-        let body = parse_body(&src[body_start..], body_length);
+        let raw_body = parse_body(&src[body_start..], body_length);
+        self.trace_io(&src[..body_start + body_length]);
+        let raw_frame = self.raw_frame(&src[..body_start + body_length]);
         src.advance(body_start + body_length);
-        Ok(Some(Event {
-            headers,
-            body: Some(body),
-        }))
+        Ok(Some(InboundResponse::new(Event::new(headers, Some(raw_body), raw_frame))))
+    }
+}
+
+/// Decodes a complete `api/response` frame to its body, through the fast path or
+/// from its parsed headers like before it
+#[cfg(feature = "bench")]
+pub fn decode_api_response(frame: &[u8], fast_path: bool) -> Bytes {
+    let mut codec = EslCodec::default();
+    let mut src = bytes::BytesMut::from(frame);
+    let response = if fast_path {
+        codec.decode_frame(&mut src)
+    } else {
+        codec.decode_headers(&mut src)
+    };
+    match response.unwrap().unwrap() {
+        InboundResponse::Api(body) => body,
+        other => other.into_event().raw_body.unwrap_or_default(),
+    }
+}
+
+impl Decoder for EslCodec {
+    type Item = InboundResponse;
+    type Error = EslError;
+    fn decode(&mut self, src: &mut bytes::BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        trace!("decode");
+        if self.resyncing && !self.resync(src) {
+            return Ok(None);
+        }
+        self.decode_frame(src)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use tokio_util::codec::Decoder;
+
+    use super::{EslCodec, InboundResponse};
+
+    #[test]
+    fn decode_disconnect_notice() {
+        let mut src = BytesMut::from(
+            "Content-Type: text/disconnect-notice\nContent-Length: 9\n\nGood bye\n".as_bytes(),
+        );
+        let response = EslCodec::default().decode(&mut src).unwrap().unwrap();
+        let InboundResponse::Disconnect(event) = response else {
+            panic!("expected disconnect, got {:?}", response);
+        };
+        assert_eq!(&Some("Good bye\n".to_string()), event.body());
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn decode_plain_event() {
+        let body =
+            "Event-Name: CHANNEL_PARK\nCaller-Caller-ID-Name: John%20Doe\nVariable-Path: 50%25\n\n";
+        let mut src = BytesMut::from(
+            format!(
+                "Content-Length: {}\nContent-Type: text/event-plain\n\n{}",
+                body.len(),
+                body
+            )
+            .as_bytes(),
+        );
+        let response = EslCodec::default().decode(&mut src).unwrap().unwrap();
+        let InboundResponse::Event(event) = response else {
+            panic!("expected event, got {:?}", response);
+        };
+        assert_eq!(Some("CHANNEL_PARK".to_string()), event.event_name());
+        assert_eq!(
+            Some("John Doe"),
+            event.headers()["Caller-Caller-ID-Name"].as_str()
+        );
+        assert_eq!(Some("50%"), event.headers()["Variable-Path"].as_str());
+        assert_eq!(&None, event.body());
+    }
+
+    #[test]
+    fn decode_command_reply() {
+        let mut src = BytesMut::from("Content-Type: command/reply\nReply-Text: +OK\n\n".as_bytes());
+        let response = EslCodec::default().decode(&mut src).unwrap().unwrap();
+        assert!(matches!(response, InboundResponse::Reply(_)));
+    }
+
+    #[test]
+    fn decode_frame_without_content_type() {
+        let mut src = BytesMut::from(
+            "+OK\n\nReply-Text: +OK accepted\n\nContent-Length: 3\n\nup\n".as_bytes(),
+        );
+        let mut codec = EslCodec::default();
+        let response = codec.decode(&mut src).unwrap().unwrap();
+        let InboundResponse::Reply(event) = response else {
+            panic!("expected reply, got {:?}", response);
+        };
+        assert!(event.headers().is_empty());
+        let event = codec.decode(&mut src).unwrap().unwrap().into_event();
+        assert_eq!(Some("+OK accepted"), event.reply_text());
+        assert_eq!(None, event.headers().get("Content-Type"));
+        let event = codec.decode(&mut src).unwrap().unwrap().into_event();
+        assert_eq!(&Some("up\n".to_string()), event.body());
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn decode_resyncs_after_corrupt_frame() {
+        let mut src = BytesMut::from(
+            "Content-Type: text/event-json\nContent-Length: 1x\n\n{\"Event-Name\":\"DTMF\"}\ngarbage\nContent-Type: command/reply\nReply-Text: +OK\n\n"
+                .as_bytes(),
+        );
+        let mut codec = EslCodec::default();
+        let response = codec.decode(&mut src).unwrap().unwrap();
+        assert!(matches!(response, InboundResponse::Corrupt(_)));
+        let event = codec.decode(&mut src).unwrap().unwrap().into_event();
+        assert_eq!(Some("+OK"), event.reply_text());
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn decode_resyncs_across_reads() {
+        let mut src = BytesMut::from("Content-Length: -\n\n{\"Event-Name\"".as_bytes());
+        let mut codec = EslCodec::default();
+        let response = codec.decode(&mut src).unwrap().unwrap();
+        assert!(matches!(response, InboundResponse::Corrupt(_)));
+        assert_eq!(None, codec.decode(&mut src).unwrap());
+        src.extend_from_slice(b":\"DTMF\"}\nContent-Ty");
+        assert_eq!(None, codec.decode(&mut src).unwrap());
+        src.extend_from_slice(b"pe: api/response\nContent-Length: 3\n\nup\n");
+        let response = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(InboundResponse::Api("up\n".into()), response);
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn decode_invalid_utf8_header() {
+        let mut src =
+            BytesMut::from(&b"Content-Type: command/reply\nReply-Text: +OK \xff\xfe\n\n"[..]);
+        let response = EslCodec::default().decode(&mut src).unwrap().unwrap();
+        let event = response.into_event();
+        assert_eq!(Some("+OK \u{fffd}\u{fffd}"), event.reply_text());
+        assert_eq!(
+            Some("command/reply"),
+            event.headers()["Content-Type"].as_str()
+        );
+    }
+
+    #[test]
+    fn decode_header_values_with_colons() {
+        let mut src = BytesMut::from(
+            "Content-Type: command/reply\nReply-Text: +OK Job-UUID: 7f4de4bc\nJob-UUID: 7f4de4bc\nno colon\n\n"
+                .as_bytes(),
+        );
+        let event = EslCodec::default()
+            .decode(&mut src)
+            .unwrap()
+            .unwrap()
+            .into_event();
+        assert_eq!(Some("+OK Job-UUID: 7f4de4bc"), event.reply_text());
+        assert_eq!(Some("7f4de4bc"), event.headers()["Job-UUID"].as_str());
+        // lines without any colon are still dropped
+        assert_eq!(3, event.headers().len());
+    }
+
+    #[test]
+    fn decode_keeps_header_order() {
+        let headers = "Reply-Text: +OK\nContent-Type: command/reply\nJob-UUID: 7f4de4bc\nSocket-Mode: async\n";
+        let mut src = BytesMut::from(format!("{}\n", headers).as_bytes());
+        let event = EslCodec::default()
+            .decode(&mut src)
+            .unwrap()
+            .unwrap()
+            .into_event();
+        let encoded: String = event
+            .headers()
+            .iter()
+            .map(|(name, value)| format!("{}: {}\n", name, value.as_str().unwrap()))
+            .collect();
+        assert_eq!(headers, encoded);
+        assert_eq!(Some("7f4de4bc"), event.headers()["Job-UUID"].as_str());
+    }
+
+    #[test]
+    fn decode_api_response_body_only() {
+        let frame = "Content-Type: api/response\nContent-Length: 14\n\n+OK [Success]\n";
+        let mut src = BytesMut::from(&frame.as_bytes()[..frame.len() - 1]);
+        assert_eq!(None, EslCodec::default().decode(&mut src).unwrap());
+        src.extend_from_slice(b"\n");
+        let response = EslCodec::default().decode(&mut src).unwrap().unwrap();
+        assert_eq!(InboundResponse::Api("+OK [Success]\n".into()), response);
+        assert!(src.is_empty());
+        let event = response.into_event();
+        assert_eq!(
+            Some("api/response"),
+            event.headers()["Content-Type"].as_str()
+        );
+        assert_eq!(Some("14"), event.headers()["Content-Length"].as_str());
+    }
+
+    #[test]
+    fn decode_api_response_like_parsed_headers() {
+        let body = "+OK [Success]\n";
+        let decode = |frame: String| {
+            let mut src = BytesMut::from(frame.as_bytes());
+            let response = EslCodec::default().decode(&mut src).unwrap().unwrap();
+            assert!(src.is_empty());
+            response
+        };
+        let fast = decode(format!(
+            "Content-Type: api/response\nContent-Length: 14\n\n{}",
+            body
+        ));
+        assert!(matches!(fast, InboundResponse::Api(_)));
+        let fast = fast.into_event();
+
+        // neither frame starts like the fast path, so their headers are parsed
+        let reordered = decode(format!(
+            "Content-Length: 14\nContent-Type: api/response\n\n{}",
+            body
+        ));
+        assert!(matches!(reordered, InboundResponse::Reply(_)));
+        let reordered = reordered.into_event();
+        assert_eq!(fast.headers(), reordered.headers());
+        assert_eq!(fast.body_bytes(), reordered.body_bytes());
+        assert_eq!(fast.raw_frame, reordered.raw_frame);
+
+        let mut extra = decode(format!(
+            "Content-Type: api/response\nX-Trace: 1\nContent-Length: 14\n\n{}",
+            body
+        ))
+        .into_event();
+        assert_eq!(Some("1"), extra.headers()["X-Trace"].as_str());
+        extra.headers.shift_remove("X-Trace");
+        assert_eq!(fast.headers(), extra.headers());
+        assert_eq!(fast.body_bytes(), extra.body_bytes());
+        assert_eq!(fast.raw_frame, extra.raw_frame);
+
+        // retained frames need the header bytes, which the fast path skips
+        let frame = format!("Content-Type: api/response\nContent-Length: 14\n\n{}", body);
+        let mut src = BytesMut::from(frame.as_bytes());
+        let retained = EslCodec::new(None, true)
+            .decode(&mut src)
+            .unwrap()
+            .unwrap()
+            .into_event();
+        assert_eq!(fast.headers(), retained.headers());
+        assert_eq!(fast.body_bytes(), retained.body_bytes());
+        assert_eq!(Some(frame.as_bytes()), retained.raw_frame.as_deref());
+    }
+
+    #[test]
+    fn decode_zero_length_bodies() {
+        let mut src = BytesMut::from(
+            "Content-Type: text/event-plain\nContent-Length: 0\n\nContent-Type: api/response\nContent-Length: 0\n\nContent-Type: command/reply\nReply-Text: +OK\n\n"
+                .as_bytes(),
+        );
+        let mut codec = EslCodec::default();
+        let event = codec.decode(&mut src).unwrap().unwrap().into_event();
+        assert_eq!(&Some(String::new()), event.body());
+        assert_eq!(Some(&b""[..]), event.body_bytes());
+        let response = codec.decode(&mut src).unwrap().unwrap();
+        assert_eq!(InboundResponse::Api(Default::default()), response);
+        let event = codec.decode(&mut src).unwrap().unwrap().into_event();
+        assert_eq!(Some("+OK"), event.reply_text());
+        assert!(src.is_empty());
     }
 }
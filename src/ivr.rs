@@ -0,0 +1,117 @@
+use std::time::Duration;
+
+use tracing::trace;
+
+use crate::{EslConnection, EslError};
+
+#[derive(Debug, Clone)]
+/// Declarative IVR menu driven by `play_and_get_digits`
+///
+/// Each valid digit maps to a value which is returned by [`IvrMenu::run`],
+/// so the caller handles the choice by matching on it.
+pub struct IvrMenu<T> {
+    prompt: String,
+    invalid_prompt: Option<String>,
+    timeout_prompt: Option<String>,
+    choices: Vec<(String, T)>,
+    tries: u8,
+    timeout: Duration,
+    terminators: String,
+}
+
+impl<T: Clone> IvrMenu<T> {
+    /// Creates menu which plays `prompt` on every try
+    pub fn new(prompt: &str) -> Self {
+        Self {
+            prompt: prompt.to_string(),
+            invalid_prompt: None,
+            timeout_prompt: None,
+            choices: Vec::new(),
+            tries: 3,
+            timeout: Duration::from_secs(5),
+            terminators: "#".to_string(),
+        }
+    }
+
+    /// Adds valid digits and the value returned when they are entered
+    pub fn choice(mut self, digits: &str, value: T) -> Self {
+        self.choices.push((digits.to_string(), value));
+        self
+    }
+
+    /// Sets prompt played after invalid digits before retrying
+    pub fn invalid_prompt(mut self, file: &str) -> Self {
+        self.invalid_prompt = Some(file.to_string());
+        self
+    }
+
+    /// Sets prompt played when no digits were entered before retrying
+    pub fn timeout_prompt(mut self, file: &str) -> Self {
+        self.timeout_prompt = Some(file.to_string());
+        self
+    }
+
+    /// Sets number of times the prompt is played, defaults to 3
+    pub fn tries(mut self, tries: u8) -> Self {
+        self.tries = tries;
+        self
+    }
+
+    /// Sets time to wait for digits after the prompt, defaults to 5 seconds
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets digits which end input early, defaults to `#`
+    pub fn terminators(mut self, terminators: &str) -> Self {
+        self.terminators = terminators.to_string();
+        self
+    }
+
+    /// Plays the menu until a valid choice is entered.
+    ///
+    /// Returns `NoInput` once all tries are used up.
+    pub async fn run(&self, conn: &EslConnection) -> Result<T, EslError> {
+        let max_digits = self
+            .choices
+            .iter()
+            .map(|(digits, _)| digits.len())
+            .max()
+            .unwrap_or(1)
+            .min(u8::MAX as usize) as u8;
+        for attempt in 1..=self.tries {
+            let digits = conn
+                .play_and_get_digits(
+                    1,
+                    max_digits,
+                    1,
+                    self.timeout.as_millis() as u64,
+                    &self.terminators,
+                    &self.prompt,
+                    "silence_stream://250",
+                )
+                .await;
+            let retry_prompt = match digits {
+                Ok(digits) => {
+                    if let Some((_, value)) = self.choices.iter().find(|(d, _)| *d == digits) {
+                        return Ok(value.clone());
+                    }
+                    trace!("invalid ivr input {} on attempt {}", digits, attempt);
+                    &self.invalid_prompt
+                }
+                Err(EslError::NoInput) => {
+                    trace!("no ivr input on attempt {}", attempt);
+                    &self.timeout_prompt
+                }
+                Err(e) => return Err(e),
+            };
+            if let Some(file) = retry_prompt {
+                if attempt < self.tries {
+                    conn.playback(file).await?;
+                }
+            }
+        }
+        Err(EslError::NoInput)
+    }
+}
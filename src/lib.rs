@@ -71,16 +71,65 @@
 //! }
 //! ```
 
+pub(crate) mod api_tools;
+pub(crate) mod app_args;
+pub(crate) mod auth;
+pub(crate) mod bridge_peers;
+pub(crate) mod call_timestamps;
 pub(crate) mod code;
+pub(crate) mod config;
 pub(crate) mod connection;
+pub(crate) mod connection_info;
+pub(crate) mod core_status;
 pub(crate) mod dp_tools;
+pub(crate) mod dtmf;
 pub(crate) mod error;
 pub(crate) mod esl;
 pub(crate) mod event;
+pub(crate) mod event_state;
+pub(crate) mod event_types;
+pub(crate) mod execute;
+pub(crate) mod heartbeat;
+pub(crate) mod hold;
 pub(crate) mod io;
+pub(crate) mod ivr;
+pub(crate) mod media_stats;
+pub(crate) mod metrics;
+pub(crate) mod originate;
+pub(crate) mod playback;
+pub(crate) mod server;
+pub(crate) mod show;
+pub(crate) mod stats;
+pub(crate) mod subscription;
 
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+/// Internals used by the benchmarks in `benches/`, not part of the public API
+pub mod bench {
+    pub use crate::io::{decode_api_response, parse_header};
+}
+
+pub use api_tools::{FileManCommand, LogLevel, PhoneEvent, IDEMPOTENCY_KEY_VARIABLE};
+pub use app_args::*;
+pub use auth::{AuthMethod, PasswordAuth};
+pub use call_timestamps::CallTimestamps;
+pub use config::{EslConfig, UnknownFramePolicy};
 pub use connection::EslConnection;
 pub use connection::EslConnectionSimple;
+pub use connection_info::ConnectionInfo;
+pub use core_status::CoreStatus;
 pub use error::*;
 pub use esl::*;
 pub use event::*;
+pub use event_state::EventState;
+pub use event_types::*;
+pub use execute::*;
+pub use ivr::*;
+pub use media_stats::{MediaDirectionStats, MediaStats};
+pub use metrics::{Metrics, NoMetrics};
+pub use originate::*;
+pub use playback::*;
+pub use server::OutboundShutdown;
+pub use show::{ShowCall, ShowRegistration};
+pub use stats::ConnectionStats;
+pub use subscription::SubscriptionGuard;
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::event::field_string;
+use crate::{EslConnection, EslError, Event};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+/// RTP quality of one direction of the audio of a channel
+pub struct MediaDirectionStats {
+    /// Mean opinion score from 1 to 5, only calculated by freeswitch for received audio
+    pub mos: Option<f64>,
+    /// Largest jitter variance in milliseconds
+    pub jitter_ms: Option<f64>,
+    /// Share of lost packets in percent
+    pub packet_loss_pct: Option<f64>,
+    /// Round trip time reported by RTCP in milliseconds
+    pub rtt_ms: Option<f64>,
+}
+
+impl MediaDirectionStats {
+    fn from_fields(fields: &HashMap<String, Value>, direction: &str) -> Self {
+        let number = |name: &str| {
+            field_string(
+                fields,
+                &format!("variable_rtp_audio_{}_{}", direction, name),
+            )?
+            .parse::<f64>()
+            .ok()
+        };
+        let packet_loss_pct = match (number("packet_count"), number("skip_packet_count")) {
+            (Some(received), Some(lost)) if received + lost > 0.0 => {
+                Some(lost * 100.0 / (received + lost))
+            }
+            (Some(_), Some(_)) => Some(0.0),
+            _ => None,
+        };
+        Self {
+            mos: number("mos"),
+            jitter_ms: number("jitter_max_variance"),
+            packet_loss_pct,
+            rtt_ms: number("rtcp_rtt"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+/// Call quality from the `rtp_audio_*` variables of a channel.
+///
+/// Freeswitch sets them at hangup, so they are in `CHANNEL_HANGUP_COMPLETE`,
+/// or on a live call after `uuid_set_media_stats`. Missing values are `None`.
+pub struct MediaStats {
+    /// Audio received from the remote party
+    pub inbound: MediaDirectionStats,
+    /// Audio sent to the remote party
+    pub outbound: MediaDirectionStats,
+}
+
+impl From<&HashMap<String, Value>> for MediaStats {
+    fn from(fields: &HashMap<String, Value>) -> Self {
+        Self {
+            inbound: MediaDirectionStats::from_fields(fields, "in"),
+            outbound: MediaDirectionStats::from_fields(fields, "out"),
+        }
+    }
+}
+
+impl From<&Event> for MediaStats {
+    fn from(event: &Event) -> Self {
+        Self::from(&event.fields())
+    }
+}
+
+impl EslConnection {
+    /// Returns current call quality of a live channel.
+    ///
+    /// Runs `uuid_set_media_stats` to refresh the variables, then reads them with `uuid_dump`.
+    pub async fn media_stats(&self, uuid: &str) -> Result<MediaStats, EslError> {
+        self.uuid_set_media_stats(uuid).await?;
+        let dump = self.uuid_api(&format!("uuid_dump {} json", uuid)).await?;
+        let fields: HashMap<String, Value> = serde_json::from_str(&dump)?;
+        Ok(MediaStats::from(&fields))
+    }
+}
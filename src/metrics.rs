@@ -0,0 +1,41 @@
+/// Hooks called by a connection, to export its stats to any metrics library.
+///
+/// All methods do nothing by default, so an implementation only overrides
+/// the ones it records. They are called inline and must not block.
+pub trait Metrics: Send + Sync {
+    /// A command was written to freeswitch
+    fn command_sent(&self) {}
+    /// The reply of a command was received
+    fn reply_received(&self) {}
+    /// Number of commands waiting for their reply changed
+    fn in_flight(&self, _commands: usize) {}
+    /// A connection was set up, counting reconnects of the application
+    fn connected(&self) {}
+    /// A frame from freeswitch couldn't be decoded
+    fn decode_error(&self) {}
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// Metrics which aren't recorded anywhere, the default of [`crate::EslConfig`]
+pub struct NoMetrics;
+
+impl Metrics for NoMetrics {}
+
+/// Shares metrics, so the application keeps a handle to read them
+impl<M: Metrics + ?Sized> Metrics for std::sync::Arc<M> {
+    fn command_sent(&self) {
+        (**self).command_sent()
+    }
+    fn reply_received(&self) {
+        (**self).reply_received()
+    }
+    fn in_flight(&self, commands: usize) {
+        (**self).in_flight(commands)
+    }
+    fn connected(&self) {
+        (**self).connected()
+    }
+    fn decode_error(&self) {
+        (**self).decode_error()
+    }
+}
@@ -0,0 +1,203 @@
+use std::fmt;
+
+use crate::api_tools::with_origination_uuid;
+use crate::app_args::quote_variable;
+use crate::{EslConnection, EslError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Where the originated call is sent once it answers
+pub enum OriginateTarget {
+    /// Extension in the dialplan, e.g. `1000 XML default`
+    Extension {
+        /// Destination number
+        extension: String,
+        /// Dialplan type, freeswitch defaults to `XML`
+        dialplan: Option<String>,
+        /// Dialplan context, freeswitch defaults to `default`
+        context: Option<String>,
+    },
+    /// Application executed on the call, e.g. `&park()`
+    Application {
+        /// Application name
+        name: String,
+        /// Application arguments
+        args: String,
+    },
+}
+
+impl OriginateTarget {
+    /// Creates target for an extension in the default dialplan and context
+    pub fn extension(extension: &str) -> Self {
+        Self::Extension {
+            extension: extension.to_string(),
+            dialplan: None,
+            context: None,
+        }
+    }
+
+    /// Creates target for an application
+    pub fn application(name: &str, args: &str) -> Self {
+        Self::Application {
+            name: name.to_string(),
+            args: args.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for OriginateTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Extension {
+                extension,
+                dialplan,
+                context,
+            } => {
+                write!(f, "{}", extension)?;
+                match (dialplan, context) {
+                    (Some(dialplan), Some(context)) => write!(f, " {} {}", dialplan, context),
+                    (Some(dialplan), None) => write!(f, " {}", dialplan),
+                    (None, Some(context)) => write!(f, " XML {}", context),
+                    (None, None) => Ok(()),
+                }
+            }
+            Self::Application { name, args } => write!(f, "&{}({})", name, args),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Call of [`EslConnection::originate`]
+pub struct OriginateOptions {
+    call_url: String,
+    target: OriginateTarget,
+    variables: Vec<(String, String)>,
+    origination_uuid: Option<String>,
+}
+
+impl OriginateOptions {
+    /// Creates call to `call_url`, e.g. `user/1000`, sent to `target` once answered
+    pub fn new(call_url: &str, target: OriginateTarget) -> Self {
+        Self {
+            call_url: call_url.to_string(),
+            target,
+            variables: Vec::new(),
+            origination_uuid: None,
+        }
+    }
+
+    /// Sets a channel variable of the new leg.
+    ///
+    /// Values containing commas, spaces or braces are quoted.
+    /// `origination_uuid` is the same as [`OriginateOptions::origination_uuid`].
+    pub fn variable(mut self, name: &str, value: &str) -> Self {
+        if name == "origination_uuid" {
+            return self.origination_uuid(value);
+        }
+        self.variables.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets uuid of the new leg, so it is known before the call exists.
+    ///
+    /// A random uuid is used if not set.
+    pub fn origination_uuid(mut self, uuid: &str) -> Self {
+        self.origination_uuid = Some(uuid.to_string());
+        self
+    }
+
+    /// Returns arguments of the `originate` command with the uuid of the new leg
+    fn to_args(&self, uuid: &str) -> String {
+        let variables: String = self
+            .variables
+            .iter()
+            .map(|(name, value)| format!("{}={},", name, quote_variable(value)))
+            .collect();
+        let call_url = with_origination_uuid(&self.call_url, &variables, uuid);
+        format!("{} {}", call_url, self.target)
+    }
+}
+
+impl EslConnection {
+    /// Originates a call and waits until it is answered. Returns uuid of the new leg.
+    ///
+    /// A call which isn't answered fails with `ApiError` of its cause, e.g. `NO_ANSWER`.
+    /// An `origination_uuid` which isn't a well-formed uuid fails with `InvalidArgument`.
+    pub async fn originate(&self, options: OriginateOptions) -> Result<String, EslError> {
+        let uuid = match &options.origination_uuid {
+            Some(uuid) => {
+                uuid::Uuid::parse_str(uuid).map_err(|e| {
+                    EslError::InvalidArgument(format!("origination_uuid {:?}: {}", uuid, e))
+                })?;
+                uuid.clone()
+            }
+            None => uuid::Uuid::new_v4().to_string(),
+        };
+        self.api(&format!("originate {}", options.to_args(&uuid)))
+            .await?;
+        Ok(uuid)
+    }
+
+    /// Schedules `originate` of `call_url` at the unix time `at_epoch`.
+    ///
+    /// Returns id of the scheduled task which can be removed with [`EslConnection::unsched`].
+    pub async fn sched_originate(
+        &self,
+        at_epoch: u64,
+        call_url: &str,
+        target: OriginateTarget,
+    ) -> Result<u32, EslError> {
+        let response = self
+            .api(&format!(
+                "sched_api {} none originate {} {}",
+                at_epoch, call_url, target
+            ))
+            .await?;
+        parse_task_id(&response)
+    }
+
+    /// Originates a leg to `dest` and bridges it to the live channel `existing_uuid`,
+    /// e.g. to add a third party to a call. Returns uuid of the new leg.
+    ///
+    /// The leg is started like [`EslConnection::bgapi_originate`], so other commands
+    /// go on while it rings, and its answer is awaited within the `bgapi_timeout`.
+    /// A far end which doesn't answer fails with `ApiError` of its cause, e.g.
+    /// `NO_ANSWER`. On any failure after the originate was sent, including a
+    /// timeout or a failed bridge, the new leg is hung up.
+    pub async fn originate_and_bridge(
+        &self,
+        dest: &str,
+        existing_uuid: &str,
+    ) -> Result<String, EslError> {
+        let uuid = uuid::Uuid::new_v4().to_string();
+        let args = format!("{} &park()", dest);
+        let started = self.bgapi_originate_as(&uuid, "", &args);
+        let answered = self
+            .background_job(&uuid, &format!("originate {}", args), started)
+            .await;
+        let bridged = match answered {
+            Ok(_) => {
+                self.uuid_api(&format!("uuid_bridge {} {}", uuid, existing_uuid))
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = bridged {
+            // the leg may exist even if its originate failed or timed out
+            let _ = self.uuid_api(&format!("uuid_kill {}", uuid)).await;
+            return Err(e);
+        }
+        Ok(uuid)
+    }
+
+    /// Removes task scheduled with [`EslConnection::sched_originate`]
+    pub async fn unsched(&self, task_id: u32) -> Result<String, EslError> {
+        self.api(&format!("sched_del {}", task_id)).await
+    }
+}
+
+pub(crate) fn parse_task_id(response: &str) -> Result<u32, EslError> {
+    // a reply of freeswitch which isn't understood is no error of the caller
+    let unexpected = || EslError::InternalError(format!("Unexpected sched_api reply {}", response));
+    let task_id = response.trim().strip_prefix("Added:").ok_or_else(unexpected)?;
+    task_id.trim().parse().map_err(|_| unexpected())
+}
@@ -0,0 +1,132 @@
+use std::future::Future;
+
+use crate::connection::execute_completion;
+use crate::{EslConnection, EslError, Event};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// Playback of a prompt with silence around it, gain and loops
+///
+/// The options are composed into a single `file_string://` so the whole
+/// prompt is played by one `playback`.
+pub struct PlaybackBuilder {
+    file: String,
+    gain_db: i8,
+    prepend_silence_ms: u32,
+    append_silence_ms: u32,
+    loops: u32,
+}
+
+impl PlaybackBuilder {
+    /// Creates playback of `file` without any options
+    pub fn new(file: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            gain_db: 0,
+            prepend_silence_ms: 0,
+            append_silence_ms: 0,
+            loops: 1,
+        }
+    }
+
+    /// Sets gain of the file, passed as its `vol` parameter
+    pub fn gain_db(mut self, gain_db: i8) -> Self {
+        self.gain_db = gain_db;
+        self
+    }
+
+    /// Sets silence played before the first loop of the file
+    pub fn prepend_silence_ms(mut self, millis: u32) -> Self {
+        self.prepend_silence_ms = millis;
+        self
+    }
+
+    /// Sets silence played after the last loop of the file
+    pub fn append_silence_ms(mut self, millis: u32) -> Self {
+        self.append_silence_ms = millis;
+        self
+    }
+
+    /// Sets number of times the file is played, defaults to 1.
+    ///
+    /// 0 is played once, as an empty `file_string://` is rejected by freeswitch.
+    pub fn loops(mut self, loops: u32) -> Self {
+        self.loops = loops.max(1);
+        self
+    }
+
+    /// Returns the composed file, which is the plain file without options
+    pub fn build(&self) -> String {
+        let file = if self.gain_db == 0 {
+            self.file.clone()
+        } else {
+            format!("{{vol={}}}{}", self.gain_db, self.file)
+        };
+        let mut parts = Vec::new();
+        if self.prepend_silence_ms > 0 {
+            parts.push(format!("silence_stream://{}", self.prepend_silence_ms));
+        }
+        parts.extend(std::iter::repeat_n(file, self.loops as usize));
+        if self.append_silence_ms > 0 {
+            parts.push(format!("silence_stream://{}", self.append_silence_ms));
+        }
+        match parts.as_slice() {
+            [part] if self.gain_db == 0 => part.clone(),
+            _ => format!("file_string://{}", parts.join("!")),
+        }
+    }
+
+    /// Plays the composed file on the call of `conn`
+    pub async fn play(&self, conn: &EslConnection) -> Result<Event, EslError> {
+        conn.playback(&self.build()).await
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Stops a playback started with [`EslConnection::playback_handle`]
+pub struct PlaybackHandle<'a> {
+    connection: &'a EslConnection,
+    call_uuid: String,
+}
+
+impl PlaybackHandle<'_> {
+    /// Cuts the playback short with `uuid_break`, after which its future resolves
+    pub async fn stop(&self) -> Result<String, EslError> {
+        self.connection
+            .uuid_api(&format!("uuid_break {}", self.call_uuid))
+            .await
+    }
+}
+
+impl EslConnection {
+    /// starts playing `file_path` in outbound mode, returning a handle to stop it
+    /// and a future resolving to its completion event.
+    ///
+    /// The playback runs with `async: true`, so it can be stopped on any
+    /// external event, e.g. hold music when an agent picks up.
+    pub async fn playback_handle(
+        &self,
+        file_path: &str,
+    ) -> Result<
+        (
+            PlaybackHandle<'_>,
+            impl Future<Output = Result<Event, EslError>> + '_,
+        ),
+        EslError,
+    > {
+        let call_uuid = self.ready_call_uuid()?.to_string();
+        let (event_uuid, rx) = self
+            .start_execute("playback", file_path, &[("async", "true")])
+            .await?;
+        let handle = PlaybackHandle {
+            connection: self,
+            call_uuid,
+        };
+        // dropping the future without a completion forgets the playback
+        let guard = self.job_guard(event_uuid);
+        let completion = async move {
+            let _guard = guard;
+            execute_completion("playback", rx).await
+        };
+        Ok((handle, completion))
+    }
+}
@@ -0,0 +1,134 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::{select, Either};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tracing::{trace, warn};
+
+use crate::{Esl, EslConfig, EslConnection, EslError};
+
+#[derive(Debug, Clone)]
+/// Drain signal and in-flight call count of [`Esl::serve_outbound`]
+pub struct OutboundShutdown {
+    drain: Arc<watch::Sender<Option<Duration>>>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Default for OutboundShutdown {
+    fn default() -> Self {
+        Self {
+            drain: Arc::new(watch::Sender::new(None)),
+            in_flight: Arc::default(),
+        }
+    }
+}
+
+impl OutboundShutdown {
+    /// Creates a signal for one server
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops accepting calls and gives running handlers `timeout` to finish before aborting them
+    pub fn drain(&self, timeout: Duration) {
+        self.drain.send_replace(Some(timeout));
+    }
+
+    /// Returns number of calls whose handler is still running
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+/// Pause after running out of file descriptors, so the accept loop does not spin
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Whether `error` is EMFILE or ENFILE, which clear only once connections close
+fn is_fd_exhausted(error: &std::io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(23 | 24))
+}
+
+/// Decrements the in-flight count when the handler task ends or is aborted
+struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    fn start(count: &Arc<AtomicUsize>) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+        Self(count.clone())
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Esl {
+    /// Accepts outbound connections on `listener` and runs `handler` for each call.
+    ///
+    /// Returns once [`OutboundShutdown::drain`] was called and every handler finished
+    /// or was aborted after the drain timeout.
+    pub async fn serve_outbound<F, Fut>(
+        listener: TcpListener,
+        config: EslConfig,
+        shutdown: OutboundShutdown,
+        handler: F,
+    ) -> Result<(), EslError>
+    where
+        F: Fn(EslConnection) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let handler = Arc::new(handler);
+        let mut drain = shutdown.drain.subscribe();
+        let mut handlers = JoinSet::new();
+        let timeout = loop {
+            let accept = Box::pin(listener.accept());
+            let drained = Box::pin(drain.wait_for(Option::is_some));
+            // drain is polled first so a pending connection is not accepted after it
+            let accepted = match select(drained, accept).await {
+                Either::Left((timeout, _)) => {
+                    break timeout
+                        .ok()
+                        .and_then(|timeout| *timeout)
+                        .unwrap_or_default()
+                }
+                Either::Right((accepted, _)) => accepted,
+            };
+            let (stream, peer) = match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    // returning here would drop and abort the running handlers
+                    warn!("accepting outbound connection failed: {}", e);
+                    if is_fd_exhausted(&e) {
+                        tokio::time::sleep(ACCEPT_BACKOFF).await;
+                    }
+                    continue;
+                }
+            };
+            trace!("accepted outbound connection from {}", peer);
+            while handlers.try_join_next().is_some() {}
+            let guard = InFlight::start(&shutdown.in_flight);
+            let handler = handler.clone();
+            let config = config.clone();
+            handlers.spawn(async move {
+                let _guard = guard;
+                match Esl::outbound_with_config(stream, config).await {
+                    Ok(connection) => handler(connection).await,
+                    Err(e) => warn!("outbound connection from {} failed: {}", peer, e),
+                }
+            });
+        };
+        drop(listener);
+        let finished = async { while handlers.join_next().await.is_some() {} };
+        if tokio::time::timeout(timeout, finished).await.is_err() {
+            warn!("aborting {} outbound handlers after drain", handlers.len());
+            handlers.shutdown().await;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::{EslConnection, EslError};
+
+/// Columns of one row of a `show` reply
+type ShowRow = HashMap<String, String>;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Row of `show registrations`
+pub struct ShowRegistration {
+    /// Value of `reg_user`
+    pub user: Option<String>,
+    /// Realm of the registration
+    pub realm: Option<String>,
+    /// Contact url to reach the user
+    pub url: Option<String>,
+    /// Unix time when the registration expires
+    pub expires: Option<u64>,
+    /// Address the registration came from
+    pub network_ip: Option<String>,
+    /// Port the registration came from
+    pub network_port: Option<String>,
+    /// Transport e.g. `udp` or `tls`
+    pub network_proto: Option<String>,
+    /// Freeswitch host holding the registration
+    pub hostname: Option<String>,
+}
+
+impl From<&ShowRow> for ShowRegistration {
+    fn from(row: &ShowRow) -> Self {
+        let column = |name: &str| column(row, name);
+        Self {
+            user: column("reg_user"),
+            realm: column("realm"),
+            url: column("url"),
+            expires: column("expires").and_then(|expires| expires.parse().ok()),
+            network_ip: column("network_ip"),
+            network_port: column("network_port"),
+            network_proto: column("network_proto"),
+            hostname: column("hostname"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+/// Row of `show calls`, a channel with its bridged leg if any
+pub struct ShowCall {
+    /// Uuid of the channel
+    pub uuid: Option<String>,
+    /// Value of `direction`, `inbound` or `outbound`
+    pub direction: Option<String>,
+    /// Unix time when the channel was created
+    pub created_epoch: Option<u64>,
+    /// Channel name e.g. `sofia/internal/1000@10.0.0.5`
+    pub name: Option<String>,
+    /// Caller id number
+    pub cid_num: Option<String>,
+    /// Dialed destination
+    pub dest: Option<String>,
+    /// Call state e.g. `ACTIVE` or `RINGING`
+    pub callstate: Option<String>,
+    /// Uuid of the bridged leg
+    pub b_uuid: Option<String>,
+}
+
+impl From<&ShowRow> for ShowCall {
+    fn from(row: &ShowRow) -> Self {
+        let column = |name: &str| column(row, name);
+        Self {
+            uuid: column("uuid"),
+            direction: column("direction"),
+            created_epoch: column("created_epoch").and_then(|created| created.parse().ok()),
+            name: column("name"),
+            cid_num: column("cid_num"),
+            dest: column("dest"),
+            callstate: column("callstate"),
+            b_uuid: column("b_uuid"),
+        }
+    }
+}
+
+fn column(row: &ShowRow, name: &str) -> Option<String> {
+    row.get(name).filter(|value| !value.is_empty()).cloned()
+}
+
+/// Parses rows of a `show` reply, which is json when the build supports
+/// `as json` for the command and the default CSV otherwise
+fn parse_show(reply: &str) -> Result<Vec<ShowRow>, EslError> {
+    let reply = reply.trim();
+    if reply.starts_with('{') {
+        return parse_show_json(reply);
+    }
+    Ok(parse_show_csv(reply))
+}
+
+fn parse_show_json(reply: &str) -> Result<Vec<ShowRow>, EslError> {
+    let json: HashMap<String, Value> = serde_json::from_str(reply)?;
+    // freeswitch leaves out `rows` when there are none
+    let Some(Value::Array(rows)) = json.get("rows") else {
+        return Ok(Vec::new());
+    };
+    Ok(rows
+        .iter()
+        .filter_map(Value::as_object)
+        .map(|row| {
+            row.iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        Value::String(value) => value.clone(),
+                        other => other.to_string(),
+                    };
+                    (name.clone(), value)
+                })
+                .collect()
+        })
+        .collect())
+}
+
+fn parse_show_csv(reply: &str) -> Vec<ShowRow> {
+    let mut lines = reply
+        .lines()
+        .filter(|line| !line.is_empty() && !line.ends_with(" total."));
+    let Some(header) = lines.next() else {
+        return Vec::new();
+    };
+    let names: Vec<&str> = header.split(',').collect();
+    // values aren't quoted, so extra commas belong to the last column
+    lines
+        .map(|line| {
+            names
+                .iter()
+                .zip(line.splitn(names.len(), ','))
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        })
+        .collect()
+}
+
+impl EslConnection {
+    /// Returns registrations of all sofia profiles
+    pub async fn show_registrations(&self) -> Result<Vec<ShowRegistration>, EslError> {
+        let rows = self.show("registrations").await?;
+        Ok(rows.iter().map(ShowRegistration::from).collect())
+    }
+
+    /// Returns active calls
+    pub async fn show_calls(&self) -> Result<Vec<ShowCall>, EslError> {
+        let rows = self.show("calls").await?;
+        Ok(rows.iter().map(ShowCall::from).collect())
+    }
+
+    async fn show(&self, what: &str) -> Result<Vec<ShowRow>, EslError> {
+        let reply = self.api(&format!("show {} as json", what)).await?;
+        parse_show(&reply)
+    }
+}
@@ -0,0 +1,65 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Snapshot of the activity of a connection, returned by [`crate::EslConnection::stats`]
+pub struct ConnectionStats {
+    /// Commands written to freeswitch, including the setup commands
+    pub commands_sent: u64,
+    /// Events received, whether or not a stream or job was waiting for them
+    pub events_received: u64,
+    /// Commands waiting for their reply
+    pub pending_commands: usize,
+    /// Background jobs and applications waiting for their completion event
+    pub pending_jobs: usize,
+    /// When the last frame was sent or received
+    pub last_activity: Instant,
+}
+
+#[derive(Debug)]
+struct Counters {
+    commands_sent: AtomicU64,
+    events_received: AtomicU64,
+    last_activity: Mutex<Instant>,
+}
+
+#[derive(Debug, Clone)]
+/// Counters of a connection, updated by its send path and reader task
+pub(crate) struct StatsCounters(Arc<Counters>);
+
+impl Default for StatsCounters {
+    fn default() -> Self {
+        Self(Arc::new(Counters {
+            commands_sent: AtomicU64::new(0),
+            events_received: AtomicU64::new(0),
+            last_activity: Mutex::new(Instant::now()),
+        }))
+    }
+}
+
+impl StatsCounters {
+    pub(crate) fn command_sent(&self) {
+        self.0.commands_sent.fetch_add(1, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub(crate) fn event_received(&self) {
+        self.0.events_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that a frame was sent or received now
+    pub(crate) fn touch(&self) {
+        *self.0.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    pub(crate) fn snapshot(&self, pending_commands: usize, pending_jobs: usize) -> ConnectionStats {
+        ConnectionStats {
+            commands_sent: self.0.commands_sent.load(Ordering::Relaxed),
+            events_received: self.0.events_received.load(Ordering::Relaxed),
+            pending_commands,
+            pending_jobs,
+            last_activity: *self.0.last_activity.lock().unwrap(),
+        }
+    }
+}
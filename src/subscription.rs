@@ -0,0 +1,169 @@
+use crate::event_types::EventSubscription;
+use crate::{EslConnection, EslError, Event};
+
+#[derive(Debug)]
+/// Events subscribed by [`EslConnection::subscribe_scoped`], unsubscribed with
+/// `nixevent` when dropped
+pub struct SubscriptionGuard<'a> {
+    connection: &'a EslConnection,
+    names: Vec<String>,
+}
+
+impl SubscriptionGuard<'_> {
+    /// Returns events which are unsubscribed when the guard is released
+    pub fn events(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Unsubscribes now and waits for the reply, instead of in the background on drop
+    pub async fn unsubscribe(mut self) -> Result<Option<Event>, EslError> {
+        let names = std::mem::take(&mut self.names);
+        let Some(message) = nixevent(&names) else {
+            return Ok(None);
+        };
+        let reply = self.connection.send_recv(message.as_bytes()).await?;
+        self.connection.unsubscribed(Some(&names));
+        Ok(Some(reply))
+    }
+}
+
+impl Drop for SubscriptionGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(message) = nixevent(&self.names) {
+            self.connection.unsubscribed(Some(&self.names));
+            self.connection.send_detached(message.into_bytes());
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+/// Union of the events subscribed on a connection, as freeswitch adds up `event`
+/// commands until `nixevent` or `noevents`
+pub(crate) struct Subscriptions {
+    events: Vec<String>,
+    subclasses: Vec<String>,
+}
+
+/// Splits arguments of an event command into event names and the subclasses
+/// following `CUSTOM`, noting whether `CUSTOM` was given
+fn split_custom(names: &[String]) -> (&[String], Option<&[String]>) {
+    match names.iter().position(|name| name == "CUSTOM") {
+        Some(custom) => (&names[..custom], Some(&names[custom + 1..])),
+        None => (names, None),
+    }
+}
+
+impl Subscriptions {
+    /// Adds arguments of an accepted `event` command
+    pub(crate) fn add(&mut self, names: &[String]) {
+        let (events, custom) = split_custom(names);
+        let bare_custom = matches!(custom, Some([])).then_some("CUSTOM");
+        for event in events.iter().map(String::as_str).chain(bare_custom) {
+            if !self.events.iter().any(|known| known == event) {
+                self.events.push(event.to_string());
+            }
+        }
+        for subclass in custom.unwrap_or_default() {
+            if !self.subclasses.contains(subclass) {
+                self.subclasses.push(subclass.clone());
+            }
+        }
+    }
+
+    /// Returns the part of `names` not subscribed yet, with subclasses after `CUSTOM`
+    pub(crate) fn missing(&self, names: &[String]) -> Vec<String> {
+        let (events, custom) = split_custom(names);
+        let has = |event: &str| self.events.iter().any(|known| known == event);
+        let mut missing: Vec<String> = events
+            .iter()
+            .filter(|event| !has("ALL") && !has(event))
+            .cloned()
+            .collect();
+        match custom {
+            Some([]) if !has("CUSTOM") => missing.push("CUSTOM".to_string()),
+            Some(subclasses) => {
+                let subclasses = subclasses
+                    .iter()
+                    .filter(|subclass| !self.subclasses.contains(subclass));
+                let mut subclasses = subclasses.cloned().peekable();
+                if subclasses.peek().is_some() {
+                    missing.push("CUSTOM".to_string());
+                    missing.extend(subclasses);
+                }
+            }
+            None => {}
+        }
+        missing
+    }
+
+    /// Removes arguments of an accepted `nixevent` command
+    pub(crate) fn remove(&mut self, names: &[String]) {
+        let (events, custom) = split_custom(names);
+        if events.iter().any(|event| event == "ALL") {
+            return self.clear();
+        }
+        self.events.retain(|event| !events.contains(event));
+        match custom {
+            Some([]) => {
+                self.events.retain(|event| event != "CUSTOM");
+                self.subclasses.clear();
+            }
+            Some(subclasses) => self.subclasses.retain(|known| !subclasses.contains(known)),
+            None => {}
+        }
+    }
+
+    /// Forgets all events after `noevents`
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+        self.subclasses.clear();
+    }
+
+    /// Returns arguments of one `event` command subscribing to all events
+    pub(crate) fn names(&self) -> Vec<String> {
+        if self.subclasses.is_empty() {
+            return self.events.clone();
+        }
+        let mut names: Vec<String> = self
+            .events
+            .iter()
+            .filter(|event| *event != "CUSTOM")
+            .cloned()
+            .collect();
+        names.push("CUSTOM".to_string());
+        names.extend(self.subclasses.iter().cloned());
+        names
+    }
+}
+
+fn nixevent(names: &[String]) -> Option<String> {
+    (!names.is_empty()).then(|| format!("nixevent {}", names.join(" ")))
+}
+
+impl EslConnection {
+    /// subscribes to given events in addition to the current subscription,
+    /// until the returned guard is dropped.
+    ///
+    /// Events already subscribed with [`EslConnection::subscribe`] stay subscribed,
+    /// subclasses are unsubscribed with `nixevent CUSTOM <subclass>`.
+    pub async fn subscribe_scoped<E: Into<EventSubscription>>(
+        &self,
+        events: impl IntoIterator<Item = E>,
+    ) -> Result<SubscriptionGuard<'_>, EslError> {
+        let names = self.subscription_names(events)?;
+        let response = self
+            .send_recv(format!("event json {}", names.join(" ")).as_bytes())
+            .await?;
+        if let Some(error) = response
+            .reply_text()
+            .and_then(|text| text.strip_prefix("-ERR"))
+        {
+            return Err(EslError::ApiError(error.trim().to_string()));
+        }
+        let names = self.subscribed(&names);
+        Ok(SubscriptionGuard {
+            connection: self,
+            names,
+        })
+    }
+}
@@ -0,0 +1,313 @@
+#![allow(dead_code)]
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+use anyhow::Result;
+use freeswitch_esl::{Esl, EslConfig, EslConnection, EslError};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+};
+
+pub const CALL_UUID: &str = "c1a1b2c3-0000-4000-8000-000000000001";
+
+/// Reply to `connect` captured from freeswitch, with `CALL_UUID` as placeholder
+const CONNECT_REPLY: &str = "Event-Name: CHANNEL_DATA
+Core-UUID: bd0e8916-6a60-4e11-8978-db8580b440a6
+FreeSWITCH-Hostname: ip-172-31-32-63
+Event-Date-Local: 2023-09-12%2004%3A31%3A37
+Event-Date-Timestamp: 1694493097638660
+Channel-State: CS_EXECUTE
+Channel-Call-State: RINGING
+Channel-State-Number: 4
+Channel-Name: sofia/internal/1000%40172.31.32.63%3A5060
+Unique-ID: CALL_UUID
+Call-Direction: inbound
+Presence-Call-Direction: inbound
+Answer-State: ringing
+Caller-Direction: inbound
+Caller-Username: 1000
+Caller-Dialplan: XML
+Caller-Caller-ID-Name: Extension%201000
+Caller-Caller-ID-Number: 1000
+Caller-Network-Addr: 172.31.32.63
+Caller-Destination-Number: 5000
+Caller-Unique-ID: CALL_UUID
+Caller-Context: default
+Channel-Unique-ID: CALL_UUID
+Content-Type: command/reply
+Reply-Text: +OK
+Socket-Mode: async
+Control: full
+
+";
+
+/// Handler deciding the frames sent back for a command. `None` falls back to the default reply.
+pub type Handler = Arc<dyn Fn(&str) -> Option<Vec<String>> + Send + Sync>;
+
+/// Mocked freeswitch side of an esl socket
+pub struct Mock {
+    received: Arc<Mutex<Vec<String>>>,
+    reads: Arc<AtomicUsize>,
+    push: UnboundedSender<Option<Vec<u8>>>,
+}
+
+impl Mock {
+    /// Commands received from the connection, in order
+    pub fn received(&self) -> Vec<String> {
+        self.received.lock().unwrap().clone()
+    }
+
+    /// Number of socket reads which returned data
+    pub fn reads(&self) -> usize {
+        self.reads.load(Ordering::SeqCst)
+    }
+
+    /// Commands received after the connection setup
+    pub fn commands(&self) -> Vec<String> {
+        self.received()
+            .into_iter()
+            .filter(|command| {
+                !command.starts_with("auth ")
+                    && command != "connect"
+                    && command != "myevents"
+                    && command != "event json BACKGROUND_JOB CHANNEL_EXECUTE_COMPLETE"
+            })
+            .collect()
+    }
+
+    /// Application names and arguments of the execute commands received
+    pub fn executed_apps(&self) -> Vec<(String, String)> {
+        self.commands()
+            .iter()
+            .filter_map(|command| {
+                let app = command_header(command, "execute-app-name")?;
+                let args = command_header(command, "execute-app-arg").unwrap_or_default();
+                Some((app.to_string(), args.to_string()))
+            })
+            .collect()
+    }
+
+    /// Sends a frame to the connection without waiting for a command
+    pub fn push(&self, frame: impl Into<String>) {
+        self.push_bytes(frame.into().into_bytes());
+    }
+
+    /// Sends raw bytes, which may be invalid UTF-8, to the connection
+    pub fn push_bytes(&self, frame: Vec<u8>) {
+        let _ = self.push.send(Some(frame));
+    }
+
+    /// Closes the socket from the freeswitch side
+    pub fn close(&self) {
+        let _ = self.push.send(None);
+    }
+}
+
+pub fn command_reply(reply_text: &str) -> String {
+    format!(
+        "Content-Type: command/reply\nReply-Text: {}\n\n",
+        reply_text
+    )
+}
+
+pub fn api_response(body: &str) -> String {
+    format!(
+        "Content-Type: api/response\nContent-Length: {}\n\n{}",
+        body.len(),
+        body
+    )
+}
+
+pub fn event_json(fields: serde_json::Value) -> String {
+    let body = fields.to_string();
+    format!(
+        "Content-Length: {}\nContent-Type: text/event-json\n\n{}",
+        body.len(),
+        body
+    )
+}
+
+/// Returns value of a header line of a command
+pub fn command_header<'a>(command: &'a str, name: &str) -> Option<&'a str> {
+    command
+        .lines()
+        .find_map(|line| line.strip_prefix(&format!("{}: ", name)))
+}
+
+/// Builds the reply and `CHANNEL_EXECUTE_COMPLETE` event for a sendmsg execute command
+pub fn execute_complete(command: &str, extra: serde_json::Value) -> Vec<String> {
+    let app_name = command_header(command, "execute-app-name").unwrap_or_default();
+    let event_uuid = command_header(command, "Event-UUID").unwrap_or_default();
+    let mut fields = serde_json::json!({
+        "Event-Name": "CHANNEL_EXECUTE_COMPLETE",
+        "Unique-ID": CALL_UUID,
+        "Application": app_name,
+        "Application-UUID": event_uuid,
+        "Application-Response": "_none_",
+    });
+    if let (Some(fields), Some(extra)) = (fields.as_object_mut(), extra.as_object()) {
+        fields.extend(extra.clone());
+    }
+    vec![command_reply("+OK"), event_json(fields)]
+}
+
+/// Replies sent for `command` when the handler doesn't answer it
+pub fn default_reply(command: &str) -> Vec<String> {
+    if command.starts_with("auth ") {
+        vec![command_reply("+OK accepted")]
+    } else if command == "connect" {
+        vec![CONNECT_REPLY.replace("CALL_UUID", CALL_UUID)]
+    } else if command.starts_with("sendmsg") && command.contains("call-command: execute") {
+        execute_complete(command, serde_json::json!({}))
+    } else {
+        vec![command_reply("+OK")]
+    }
+}
+
+/// Length of the complete message starting `data`, including its `content-length` body,
+/// after dropping the blank lines freeswitch skips before a command
+fn message_length(data: &mut Vec<u8>) -> Option<usize> {
+    let blank = data.iter().take_while(|byte| **byte == b'\n').count();
+    data.drain(..blank);
+    let index = data.windows(2).position(|window| window == b"\n\n")?;
+    let headers = String::from_utf8_lossy(&data[..index]).to_lowercase();
+    let Some(body) = command_header(&headers, "content-length") else {
+        return Some(index);
+    };
+    let length = index + 2 + body.parse::<usize>().unwrap();
+    (data.len() >= length).then_some(length)
+}
+
+async fn serve(
+    mut socket: TcpStream,
+    handler: Handler,
+    received: Arc<Mutex<Vec<String>>>,
+    reads: Arc<AtomicUsize>,
+    mut pushed: UnboundedReceiver<Option<Vec<u8>>>,
+) {
+    let mut buffer = [0; 4096];
+    let mut data = Vec::new();
+    loop {
+        tokio::select! {
+            frame = pushed.recv() => match frame {
+                Some(Some(frame)) => {
+                    if socket.write_all(&frame).await.is_err() {
+                        return;
+                    }
+                }
+                _ => return,
+            },
+            read = socket.read(&mut buffer) => {
+                let n = match read {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => n,
+                };
+                reads.fetch_add(1, Ordering::SeqCst);
+                data.extend_from_slice(&buffer[..n]);
+                while let Some(length) = message_length(&mut data) {
+                    let command = String::from_utf8_lossy(&data[..length]).to_string();
+                    data.drain(..length);
+                    received.lock().unwrap().push(command.clone());
+                    let replies = handler(&command).unwrap_or_else(|| default_reply(&command));
+                    for reply in replies {
+                        if socket.write_all(reply.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn spawn_mock(socket: TcpStream, handler: Handler) -> Mock {
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let reads = Arc::new(AtomicUsize::new(0));
+    let (push, pushed) = unbounded_channel();
+    tokio::spawn(serve(
+        socket,
+        handler,
+        Arc::clone(&received),
+        Arc::clone(&reads),
+        pushed,
+    ));
+    Mock {
+        received,
+        reads,
+        push,
+    }
+}
+
+/// Raw socket connected to a mock which already sent the auth request
+pub async fn inbound_stream<F>(handler: F) -> Result<(TcpStream, Mock)>
+where
+    F: Fn(&str) -> Option<Vec<String>> + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("localhost:0").await?;
+    let stream = TcpStream::connect(listener.local_addr()?).await?;
+    let (mut socket, _) = listener.accept().await?;
+    socket.write_all(b"Content-Type: auth/request\n\n").await?;
+    Ok((stream, spawn_mock(socket, Arc::new(handler))))
+}
+
+/// Inbound connection authenticated against a mock
+pub async fn inbound<F>(handler: F) -> Result<(EslConnection, Mock)>
+where
+    F: Fn(&str) -> Option<Vec<String>> + Send + Sync + 'static,
+{
+    let (stream, mock) = inbound_stream(handler).await?;
+    let connection = Esl::inbound(stream, "ClueCon").await?;
+    Ok((connection, mock))
+}
+
+/// Outbound connection accepted from a mock acting as a call
+pub async fn outbound<F>(handler: F) -> Result<(EslConnection, Mock)>
+where
+    F: Fn(&str) -> Option<Vec<String>> + Send + Sync + 'static,
+{
+    let (connection, mock) = outbound_with_config(handler, EslConfig::default()).await;
+    Ok((connection?, mock))
+}
+
+/// Outbound connection with options, returning the mock even when setup fails
+pub async fn outbound_with_config<F>(
+    handler: F,
+    config: EslConfig,
+) -> (Result<EslConnection, EslError>, Mock)
+where
+    F: Fn(&str) -> Option<Vec<String>> + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("localhost:0").await.unwrap();
+    let socket = TcpStream::connect(listener.local_addr().unwrap())
+        .await
+        .unwrap();
+    let (stream, _) = listener.accept().await.unwrap();
+    let mock = spawn_mock(socket, Arc::new(handler));
+    let connection = Esl::outbound_with_config(stream, config).await;
+    (connection, mock)
+}
+
+/// Mock acting as a call which dials an outbound server at `addr`
+pub async fn call<F>(addr: std::net::SocketAddr, handler: F) -> Result<Mock>
+where
+    F: Fn(&str) -> Option<Vec<String>> + Send + Sync + 'static,
+{
+    let socket = TcpStream::connect(addr).await?;
+    Ok(spawn_mock(socket, Arc::new(handler)))
+}
+
+/// Builds the completion of `play_and_get_digits` storing `digits` in its variable
+pub fn digits_entered(command: &str, digits: Option<&str>) -> Vec<String> {
+    let args = command_header(command, "execute-app-arg").unwrap_or_default();
+    let variable = args.split_whitespace().nth(7).unwrap_or_default();
+    let mut fields = serde_json::Map::new();
+    if let Some(digits) = digits {
+        fields.insert(format!("variable_{}", variable), digits.into());
+    }
+    execute_complete(command, serde_json::Value::Object(fields))
+}
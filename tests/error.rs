@@ -0,0 +1,75 @@
+use freeswitch_esl::{EslError, ResponseCode};
+
+#[derive(Debug)]
+struct CrmError;
+
+impl std::fmt::Display for CrmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "crm lookup failed")
+    }
+}
+
+impl std::error::Error for CrmError {}
+
+fn lookup() -> Result<(), CrmError> {
+    Err(CrmError)
+}
+
+fn parse_volume(volume: &str) -> Result<f32, EslError> {
+    Ok(volume.parse::<f32>()?)
+}
+
+fn parse_digits(digits: &str) -> Result<u32, EslError> {
+    Ok(digits.parse::<u32>()?)
+}
+
+#[test]
+fn arbitrary_error_into_other() {
+    let error = lookup().map_err(EslError::other).unwrap_err();
+    assert_eq!(EslError::Other("crm lookup failed".into()), error);
+    assert_eq!("crm lookup failed", error.to_string());
+}
+
+#[test]
+fn parse_error_into_other() {
+    assert_eq!(Ok(0.5), parse_volume("0.5"));
+    assert_eq!(
+        Err(EslError::Other("invalid float literal".into())),
+        parse_volume("loud")
+    );
+    // every parse error of a handler ends up in the same variant
+    assert_eq!(
+        Err(EslError::Other("invalid digit found in string".into())),
+        parse_digits("12#")
+    );
+}
+
+#[test]
+fn api_error_response_codes() {
+    let cases = [
+        ("No such channel!", Some(ResponseCode::NoSuchChannel)),
+        ("NO_SUCH_CHANNEL", Some(ResponseCode::NoSuchChannel)),
+        ("-USAGE: <uuid> [cause]", Some(ResponseCode::InvalidSyntax)),
+        ("Invalid syntax", Some(ResponseCode::InvalidSyntax)),
+        ("Permission denied!", Some(ResponseCode::PermissionDenied)),
+        (
+            "frobnicate Command not found!",
+            Some(ResponseCode::CommandNotFound),
+        ),
+        ("SUBSCRIBER_ABSENT", Some(ResponseCode::Other)),
+    ];
+    for (reason, code) in cases {
+        let error = EslError::ApiError(reason.into());
+        assert_eq!(code, error.response_code(), "{}", reason);
+        assert_eq!(format!("{:?}", reason), error.to_string());
+    }
+    assert_eq!(None, EslError::NoInput.response_code());
+}
+
+#[tokio::test]
+async fn dropped_sender_is_disconnected() {
+    let (tx, rx) = tokio::sync::oneshot::channel::<()>();
+    drop(tx);
+    let error = EslError::from(rx.await.unwrap_err());
+    assert_eq!(EslError::Disconnected, error);
+}
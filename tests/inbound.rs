@@ -1,4 +1,11 @@
+mod common;
+
 use std::net::SocketAddr;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, UNIX_EPOCH};
 
 use ntest::timeout;
 use regex::Regex;
@@ -7,16 +14,25 @@ use tokio::{
     net::{TcpListener, TcpStream},
     task::JoinHandle,
 };
+use tokio_stream::StreamExt;
 
 use anyhow::Result;
-use freeswitch_esl::{Esl, EslError};
+use freeswitch_esl::{
+    AuthMethod, BridgeEvent, CallTimestamps, ChannelLifecycleEvent, ConferenceEvent,
+    ConferenceMember, CoreStatus, Esl, EslConfig, EslConnection, EslError, EventState,
+    EventSubscription, FileManCommand, LogLevel, MediaDirectionStats, Metrics, OriginateOptions,
+    OriginateTarget, PhoneEvent, Preauthenticated, RegistrationAction, RegistrationEvent, ShowCall,
+    ShowRegistration, UnknownFramePolicy, IDEMPOTENCY_KEY_VARIABLE,
+};
 
 async fn mock_test_server() -> Result<(JoinHandle<()>, SocketAddr)> {
     let listener = TcpListener::bind("localhost:0").await?;
     let local_address = listener.local_addr()?;
     let server = tokio::spawn(async move {
+        let re = Regex::new(r"(?P<bgapi>.+)\nJob-UUID: (?P<uuid>[0-9a-fA-F-]+)").unwrap();
         loop {
             let (mut socket, _) = listener.accept().await.unwrap();
+            let re = re.clone();
             tokio::spawn(async move {
                 let _ = socket.write_all(b"Content-Type: auth/request\n\n").await;
 
@@ -46,9 +62,6 @@ async fn mock_test_server() -> Result<(JoinHandle<()>, SocketAddr)> {
                         let response_text: Vec<String> = if data_string.starts_with("bgapi")
                             && data_string.contains("Job-UUID")
                         {
-                            let re =
-                                Regex::new(r"(?P<bgapi>.+)\nJob-UUID: (?P<uuid>[0-9a-fA-F-]+)")
-                                    .unwrap();
                             let captures = re.captures(&data_string).unwrap();
                             // Extract components
                             let _ = &captures["bgapi"];
@@ -253,6 +266,23 @@ async fn connected_status() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+#[timeout(10000)]
+async fn close_shared_connection() -> Result<()> {
+    let (_, addr) = mock_test_server().await?;
+    let stream = TcpStream::connect(addr).await?;
+    let inbound = Arc::new(Esl::inbound(stream, "ClueCon").await?);
+    let other = Arc::clone(&inbound);
+    inbound.close().await?;
+    assert!(!other.connected());
+    assert_eq!(Err(EslError::NotConnected), other.api("status").await);
+    assert_eq!(
+        Err(EslError::NotConnected),
+        other.send_batch(&[b"event plain ALL"]).await.map(|_| ())
+    );
+    Ok(())
+}
+
 #[tokio::test]
 #[timeout(10000)]
 async fn restart_external_profile() -> Result<()> {
@@ -282,3 +312,2283 @@ async fn uuid_kill() -> Result<()> {
     assert_eq!("", uuid_kill_response);
     Ok(())
 }
+
+#[tokio::test]
+#[timeout(10000)]
+async fn cancel_originate_before_answer() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| {
+        if command.starts_with("bgapi originate") {
+            let job_uuid = common::command_header(command, "Job-UUID").unwrap();
+            Some(vec![format!(
+                "Content-Type: command/reply\nReply-Text: +OK Job-UUID: {}\nJob-UUID: {}\n\n",
+                job_uuid, job_uuid
+            )])
+        } else if command.starts_with("api uuid_kill") {
+            Some(vec![common::api_response("+OK\n")])
+        } else {
+            None
+        }
+    })
+    .await?;
+    let job_uuid = inbound
+        .bgapi_originate("{ignore_early_media=true}user/1000 &park")
+        .await?;
+    inbound.cancel_originate(&job_uuid).await?;
+    assert_eq!(
+        vec![
+            format!(
+                "bgapi originate {{origination_uuid={},ignore_early_media=true}}user/1000 &park\nJob-UUID: {}",
+                job_uuid, job_uuid
+            ),
+            format!("api uuid_kill {} ORIGINATOR_CANCEL", job_uuid),
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn cancel_originate_without_leg() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|command| {
+        command
+            .starts_with("api uuid_kill")
+            .then(|| vec![common::api_response("-ERR No such channel!\n")])
+    })
+    .await?;
+    let job_uuid = inbound.bgapi_originate("user/1000 &park").await?;
+    assert_eq!(
+        Err(EslError::ApiError("No such channel!".into())),
+        inbound.cancel_originate(&job_uuid).await
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn disconnect_notice() -> Result<()> {
+    let (inbound, mock) = common::inbound(|_| None).await?;
+    assert!(inbound.connected());
+    mock.push("Content-Type: text/disconnect-notice\nContent-Length: 9\n\nGood bye\n");
+    while inbound.connected() {
+        tokio::task::yield_now().await;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn pending_command_fails_on_disconnect() -> Result<()> {
+    let (inbound, mock) =
+        common::inbound(|command| (command == "api pause").then(Vec::new)).await?;
+    let pending = inbound.api("pause");
+    let close = async {
+        while !mock.commands().contains(&"api pause".to_string()) {
+            tokio::task::yield_now().await;
+        }
+        mock.close();
+    };
+    let (result, _) = tokio::join!(pending, close);
+    assert_eq!(Err(EslError::Disconnected), result);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn command_after_peer_closed_fails() -> Result<()> {
+    let (inbound, mock) = common::inbound(|_| None).await?;
+    mock.close();
+    while inbound.connected() {
+        tokio::task::yield_now().await;
+    }
+    // no reader is left to reply, so the command must not wait for one
+    assert_eq!(Err(EslError::Disconnected), inbound.api("status").await);
+    assert_eq!(Err(EslError::Disconnected), inbound.api("status").await);
+    Ok(())
+}
+
+const LOG_FRAME: &str = "Content-Type: log/data\nContent-Length: 10\nLog-Level: 7\n\nhello log\n";
+
+/// Inbound mock which sends a log frame while `api one` and `api two` both wait
+async fn logging_inbound(policy: UnknownFramePolicy) -> Result<(EslConnection, common::Mock)> {
+    let (stream, mock) = common::inbound_stream(|command| match command {
+        "api one" => Some(Vec::new()),
+        "api two" => Some(vec![
+            LOG_FRAME.to_string(),
+            common::api_response("+OK one"),
+            common::api_response("+OK two"),
+        ]),
+        _ => None,
+    })
+    .await?;
+    let config = EslConfig::new().unknown_frame_policy(policy);
+    let inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+    Ok((inbound, mock))
+}
+
+/// Sends `api one` and `api two` so both wait when the log frame arrives
+async fn api_one_two(
+    inbound: &EslConnection,
+    mock: &common::Mock,
+) -> [Result<String, EslError>; 2] {
+    let one = inbound.api("one");
+    let two = async {
+        while !mock.commands().contains(&"api one".to_string()) {
+            tokio::task::yield_now().await;
+        }
+        inbound.api("two").await
+    };
+    let (one, two) = tokio::join!(one, two);
+    [one, two]
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn unknown_frame_policies() -> Result<()> {
+    let (inbound, mock) = logging_inbound(UnknownFramePolicy::Ignore).await?;
+    assert_eq!(
+        [Ok("one".into()), Ok("two".into())],
+        api_one_two(&inbound, &mock).await
+    );
+
+    let (inbound, mock) = logging_inbound(UnknownFramePolicy::RouteToEvents).await?;
+    let mut events = Box::pin(inbound.events());
+    assert_eq!(
+        [Ok("one".into()), Ok("two".into())],
+        api_one_two(&inbound, &mock).await
+    );
+    let log = events.next().await.unwrap();
+    assert_eq!(Some("log/data"), log.headers()["Content-Type"].as_str());
+    assert_eq!(&Some("hello log\n".to_string()), log.body());
+
+    // no command gets the frame as its reply, both fail with the connection
+    let (inbound, mock) = logging_inbound(UnknownFramePolicy::Error).await?;
+    assert_eq!(
+        [Err(EslError::Disconnected), Err(EslError::Disconnected)],
+        api_one_two(&inbound, &mock).await
+    );
+    assert!(!inbound.connected());
+    assert_eq!(Err(EslError::Disconnected), inbound.api("status").await);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn on_disconnect_callback() -> Result<()> {
+    for notice in [false, true] {
+        let disconnects = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&disconnects);
+        let config = EslConfig::new().on_disconnect(move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let (stream, mock) = common::inbound_stream(|_| None).await?;
+        let inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+        assert_eq!(0, disconnects.load(Ordering::SeqCst));
+        if notice {
+            mock.push("Content-Type: text/disconnect-notice\nContent-Length: 9\n\nGood bye\n");
+        }
+        mock.close();
+        while inbound.connected() {
+            tokio::task::yield_now().await;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(1, disconnects.load(Ordering::SeqCst));
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn recordings() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|command| match command {
+        "api uuid_buglist recording" => Some(vec![common::api_response(
+            "<media-bugs>\n<media-bug>\n <function>session_record</function>\n <target>/recordings/a.wav</target>\n <thread-locked>0</thread-locked>\n</media-bug>\n<media-bug>\n <function>displace</function>\n <target>/sounds/moh.wav</target>\n <thread-locked>0</thread-locked>\n</media-bug>\n<media-bug>\n <function>session_record</function>\n <target>/recordings/b.wav</target>\n <thread-locked>0</thread-locked>\n</media-bug>\n</media-bugs>\n",
+        )]),
+        "api uuid_buglist idle" => Some(vec![common::api_response("<media-bugs>\n</media-bugs>\n")]),
+        _ => None,
+    })
+    .await?;
+    assert_eq!(
+        vec!["/recordings/a.wav", "/recordings/b.wav"],
+        inbound.recordings("recording").await?
+    );
+    assert!(inbound.recordings("idle").await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn send_batch_single_write() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| {
+        command
+            .strip_prefix("api echo ")
+            .map(|text| vec![common::api_response(&format!("+OK {}\n", text))])
+    })
+    .await?;
+    let reads = mock.reads();
+    let replies = inbound
+        .send_batch(&[b"api echo one", b"api echo two", b"api echo three"])
+        .await?;
+    let bodies: Vec<_> = replies
+        .iter()
+        .map(|reply| reply.body().clone().unwrap())
+        .collect();
+    assert_eq!(vec!["+OK one\n", "+OK two\n", "+OK three\n"], bodies);
+    assert_eq!(reads + 1, mock.reads());
+
+    let reads = mock.reads();
+    for command in ["echo one", "echo two", "echo three"] {
+        inbound.api(command).await?;
+    }
+    assert_eq!(reads + 3, mock.reads());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn uuid_debug_media() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| {
+        command
+            .starts_with("api uuid_debug_media")
+            .then(|| vec![common::api_response("+OK\n")])
+    })
+    .await?;
+    inbound.uuid_debug_media("call", true, false, true).await?;
+    inbound.uuid_debug_media("call", false, true, true).await?;
+    inbound.uuid_debug_media("call", true, true, false).await?;
+    assert_eq!(
+        Err(EslError::InvalidArgument(
+            "uuid_debug_media needs read or write".into()
+        )),
+        inbound.uuid_debug_media("call", false, false, true).await
+    );
+    assert_eq!(
+        vec![
+            "api uuid_debug_media call read on",
+            "api uuid_debug_media call write on",
+            "api uuid_debug_media call both off",
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn sched_originate() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| {
+        if command.starts_with("api sched_api") {
+            Some(vec![common::api_response("+OK Added: 42\n")])
+        } else if command.starts_with("api sched_del") {
+            Some(vec![common::api_response("+OK Deleted: 42\n")])
+        } else {
+            None
+        }
+    })
+    .await?;
+    let task_id = inbound
+        .sched_originate(1700000000, "user/1000", OriginateTarget::extension("5000"))
+        .await?;
+    assert_eq!(42, task_id);
+    inbound
+        .sched_originate(
+            1700000060,
+            "user/1001",
+            OriginateTarget::application("playback", "ivr/ivr-welcome.wav"),
+        )
+        .await?;
+    inbound.unsched(task_id).await?;
+    assert_eq!(
+        vec![
+            "api sched_api 1700000000 none originate user/1000 5000",
+            "api sched_api 1700000060 none originate user/1001 &playback(ivr/ivr-welcome.wav)",
+            "api sched_del 42",
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[test]
+fn originate_target_display() {
+    let target = OriginateTarget::Extension {
+        extension: "5000".into(),
+        dialplan: None,
+        context: Some("public".into()),
+    };
+    assert_eq!("5000 XML public", target.to_string());
+    assert_eq!(
+        "&park()",
+        OriginateTarget::application("park", "").to_string()
+    );
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn trace_io_captures_handshake() -> Result<()> {
+    let (stream, _mock) = common::inbound_stream(|_| None).await?;
+    let (writer, mut reader) = tokio::io::duplex(64 * 1024);
+    let config = EslConfig::new().trace_io(writer);
+    let _inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+    let expected = "Content-Type: auth/request\n\n\
+        auth ClueCon\n\n\
+        Content-Type: command/reply\nReply-Text: +OK accepted\n\n\
+        event json BACKGROUND_JOB CHANNEL_EXECUTE_COMPLETE\n\n\
+        Content-Type: command/reply\nReply-Text: +OK\n\n";
+    let mut captured = Vec::new();
+    while captured.len() < expected.len() {
+        let mut buffer = [0; 1024];
+        let n = reader.read(&mut buffer).await?;
+        captured.extend_from_slice(&buffer[..n]);
+    }
+    assert_eq!(expected, String::from_utf8_lossy(&captured));
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn uuid_api_failure_inside_ok() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|command| match command {
+        "api uuid_debug_media gone both on" => {
+            Some(vec![common::api_response("+OK -ERR No such channel!\n")])
+        }
+        "api uuid_debug_media bad both on" => Some(vec![common::api_response(
+            "-USAGE: <uuid> <read|write|both|vread|vwrite|vboth|all> <on|off>\n",
+        )]),
+        _ => None,
+    })
+    .await?;
+    assert_eq!(
+        Err(EslError::ApiError("No such channel!".into())),
+        inbound.uuid_debug_media("gone", true, true, true).await
+    );
+    assert_eq!(
+        Err(EslError::ApiError(
+            "<uuid> <read|write|both|vread|vwrite|vboth|all> <on|off>".into()
+        )),
+        inbound.uuid_debug_media("bad", true, true, true).await
+    );
+    Ok(())
+}
+
+fn busy_once() -> impl Fn(&str) -> Option<Vec<String>> {
+    let rejected = std::sync::atomic::AtomicBool::new(false);
+    move |command| {
+        (command.starts_with("auth ") && !rejected.swap(true, std::sync::atomic::Ordering::SeqCst))
+            .then(|| vec![common::command_reply("-ERR too busy")])
+    }
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn auth_retry_after_transient_rejection() -> Result<()> {
+    let (stream, mock) = common::inbound_stream(busy_once()).await?;
+    let config = EslConfig::new().auth_retries(2, Duration::from_millis(10));
+    let inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+    assert!(inbound.connected());
+    let auths = mock
+        .received()
+        .iter()
+        .filter(|command| command.starts_with("auth "))
+        .count();
+    assert_eq!(2, auths);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn auth_transient_rejection_without_retries() -> Result<()> {
+    let (stream, _mock) = common::inbound_stream(busy_once()).await?;
+    let inbound = Esl::inbound(stream, "ClueCon").await;
+    assert_eq!(EslError::AuthFailed, inbound.unwrap_err());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn uuid_fileman() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| {
+        command
+            .starts_with("api uuid_fileman")
+            .then(|| vec![common::api_response("+OK\n")])
+    })
+    .await?;
+    inbound
+        .uuid_fileman("call", FileManCommand::Seek(1000))
+        .await?;
+    inbound
+        .uuid_fileman("call", FileManCommand::Seek(-500))
+        .await?;
+    inbound
+        .uuid_fileman("call", FileManCommand::Speed(1))
+        .await?;
+    inbound
+        .uuid_fileman("call", FileManCommand::Speed(-2))
+        .await?;
+    inbound.uuid_fileman("call", FileManCommand::Pause).await?;
+    assert_eq!(
+        vec![
+            "api uuid_fileman call seek:+1000",
+            "api uuid_fileman call seek:-500",
+            "api uuid_fileman call speed:+1",
+            "api uuid_fileman call speed:-2",
+            "api uuid_fileman call pause",
+        ],
+        mock.commands()
+    );
+    assert_eq!("volume:+0", FileManCommand::Volume(0).to_string());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn inbound_preauthed() -> Result<()> {
+    let (mut stream, mock) = common::inbound_stream(|command| {
+        (command == "api status").then(|| vec![common::api_response("+OK UP 0 years\n")])
+    })
+    .await?;
+    let mut handshake = Vec::new();
+    let mut buffer = [0; 1024];
+    while !handshake.ends_with(b"\n\n") {
+        let n = stream.read(&mut buffer).await?;
+        handshake.extend_from_slice(&buffer[..n]);
+    }
+    assert_eq!(b"Content-Type: auth/request\n\n", &handshake[..]);
+    stream.write_all(b"auth ClueCon\n\n").await?;
+    handshake.clear();
+    while !handshake.ends_with(b"\n\n") {
+        let n = stream.read(&mut buffer).await?;
+        handshake.extend_from_slice(&buffer[..n]);
+    }
+    let inbound =
+        Esl::inbound_preauthed(Preauthenticated::assume(stream), EslConfig::new()).await?;
+    assert!(inbound.connected());
+    assert_eq!("UP 0 years", inbound.api("status").await?);
+    assert_eq!(
+        vec![
+            "auth ClueCon",
+            "event json BACKGROUND_JOB CHANNEL_EXECUTE_COMPLETE",
+            "api status"
+        ],
+        mock.received()
+    );
+    Ok(())
+}
+
+/// Reads `TCP_NODELAY` of a socket which is owned elsewhere
+#[cfg(unix)]
+fn nodelay_of(fd: std::os::fd::RawFd) -> std::io::Result<bool> {
+    use std::os::fd::FromRawFd;
+    // Not dropped, so the socket is not closed under the connection
+    let socket = std::mem::ManuallyDrop::new(unsafe { std::net::TcpStream::from_raw_fd(fd) });
+    socket.nodelay()
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[timeout(10000)]
+async fn tcp_nodelay() -> Result<()> {
+    use std::os::fd::AsRawFd;
+    let (stream, _mock) = common::inbound_stream(|_| None).await?;
+    let fd = stream.as_raw_fd();
+    let _inbound = Esl::inbound(stream, "ClueCon").await?;
+    assert!(nodelay_of(fd)?);
+
+    let (stream, _mock) = common::inbound_stream(|_| None).await?;
+    let fd = stream.as_raw_fd();
+    let config = EslConfig::new().tcp_nodelay(false).read_buffer_capacity(64);
+    let inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+    assert!(!nodelay_of(fd)?);
+    assert!(inbound.connected());
+    Ok(())
+}
+
+/// Inbound connection to a mock which never answers commands starting with `hang`
+async fn hanging_inbound(config: EslConfig) -> Result<(EslConnection, common::Mock)> {
+    let (stream, mock) = common::inbound_stream(|command| {
+        (command.starts_with("hang") || command.starts_with("api hang")).then(Vec::new)
+    })
+    .await?;
+    let inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+    Ok((inbound, mock))
+}
+
+fn timed_out(result: Result<impl std::fmt::Debug, EslError>) -> String {
+    match result {
+        Err(EslError::Timeout { command, .. }) => command,
+        other => panic!("expected timeout, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn api_timeout() -> Result<()> {
+    let config = EslConfig::new()
+        .api_timeout(Duration::from_millis(50))
+        .command_timeout(Duration::from_secs(5));
+    let (inbound, _mock) = hanging_inbound(config).await?;
+    let error = inbound.api("hang up").await.unwrap_err();
+    assert_eq!("api hang up timed out after 50ms", error.to_string());
+    assert_eq!("api hang up", timed_out(Err::<(), _>(error)));
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn slow_api_within_api_timeout() -> Result<()> {
+    let config = EslConfig::new()
+        .api_timeout(Duration::from_secs(5))
+        .command_timeout(Duration::from_millis(50));
+    let (inbound, mock) = hanging_inbound(config).await?;
+    let (response, _) = tokio::join!(inbound.api("hang originate"), async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        mock.push(common::api_response("+OK done\n"));
+    });
+    assert_eq!(Ok("done".into()), response);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn command_timeout() -> Result<()> {
+    let config = EslConfig::new()
+        .api_timeout(Duration::from_secs(5))
+        .command_timeout(Duration::from_millis(50));
+    let (inbound, _mock) = hanging_inbound(config).await?;
+    assert_eq!("hang", timed_out(inbound.send_recv(b"hang").await));
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn bgapi_timeout() -> Result<()> {
+    let config = EslConfig::new()
+        .bgapi_timeout(Duration::from_millis(50))
+        .command_timeout(Duration::from_secs(5));
+    // the bgapi is acknowledged, but its BACKGROUND_JOB never arrives
+    let (inbound, mock) = hanging_inbound(config).await?;
+    assert_eq!("status", timed_out(inbound.bgapi("status").await));
+    assert_eq!(0, inbound.stats().await.pending_jobs);
+    assert_eq!(
+        vec!["bgapi status"],
+        mock.commands()
+            .iter()
+            .map(|c| c.lines().next().unwrap())
+            .collect::<Vec<_>>()
+    );
+    Ok(())
+}
+
+/// Inbound mock of freeswitch where originated legs stay in `channels` with their key
+async fn dialer_inbound(
+    channels: Arc<Mutex<Vec<(String, String)>>>,
+) -> Result<(EslConnection, common::Mock)> {
+    common::inbound(move |command| {
+        if command.starts_with("bgapi originate") {
+            let job_uuid = common::command_header(command, "Job-UUID").unwrap();
+            let key = command
+                .split_once(&format!("{}=", IDEMPOTENCY_KEY_VARIABLE))
+                .and_then(|(_, rest)| rest.split_once(','))
+                .map(|(key, _)| key.to_string())
+                .unwrap_or_default();
+            channels.lock().unwrap().push((job_uuid.to_string(), key));
+            Some(vec![common::command_reply(&format!(
+                "+OK Job-UUID: {}",
+                job_uuid
+            ))])
+        } else if let Some(args) = command.strip_prefix("api uuid_getvar ") {
+            let (uuid, variable) = args.split_once(' ').unwrap();
+            assert_eq!(IDEMPOTENCY_KEY_VARIABLE, variable);
+            let reply = match channels.lock().unwrap().iter().find(|(c, _)| c == uuid) {
+                Some((_, key)) => key.clone(),
+                None => "-ERR No such channel!".to_string(),
+            };
+            Some(vec![common::api_response(&reply)])
+        } else {
+            None
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn idempotent_originate_after_reconnect() -> Result<()> {
+    let channels = Arc::new(Mutex::new(Vec::new()));
+    let (first, first_mock) = dialer_inbound(Arc::clone(&channels)).await?;
+    let uuid = first
+        .bgapi_originate_idempotent("user/1000 &park", "campaign-7/lead-42")
+        .await?
+        .unwrap();
+    assert_eq!(
+        format!(
+            "bgapi originate {{{}=campaign-7/lead-42,origination_uuid={}}}user/1000 &park\nJob-UUID: {}",
+            IDEMPOTENCY_KEY_VARIABLE, uuid, uuid
+        ),
+        first_mock.commands()[1]
+    );
+    drop(first);
+
+    let (second, second_mock) = dialer_inbound(Arc::clone(&channels)).await?;
+    let retry = second
+        .bgapi_originate_idempotent("user/1000 &park", "campaign-7/lead-42")
+        .await?;
+    assert_eq!(None, retry);
+    assert_eq!(
+        vec![format!(
+            "api uuid_getvar {} {}",
+            uuid, IDEMPOTENCY_KEY_VARIABLE
+        )],
+        second_mock.commands()
+    );
+    let other = second
+        .bgapi_originate_idempotent("user/1001 &park", "campaign-7/lead-43")
+        .await?;
+    assert!(other.is_some_and(|other| other != uuid));
+    assert_eq!(2, channels.lock().unwrap().len());
+
+    let sent = second_mock.commands().len();
+    for key in [
+        "", "lead 42", "lead\n42", "lead\r42", "lead=42", "lead,42", "lead'42", "lead\"42",
+    ] {
+        assert!(matches!(
+            second
+                .bgapi_originate_idempotent("user/1000 &park", key)
+                .await,
+            Err(EslError::InvalidArgument(_))
+        ));
+    }
+    assert_eq!(sent, second_mock.commands().len());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn bgapi_with_job() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| {
+        let job_uuid = common::command_header(command, "Job-UUID")?;
+        Some(vec![
+            common::command_reply(&format!("+OK Job-UUID: {}", job_uuid)),
+            common::event_json(serde_json::json!({
+                "Event-Name": "BACKGROUND_JOB",
+                "Job-UUID": job_uuid,
+                "_body": "+OK 7f4de4bc\n",
+            })),
+        ])
+    })
+    .await?;
+    let (job_uuid, body) = inbound.bgapi_with_job("originate user/1000 &park").await?;
+    assert_eq!("7f4de4bc", body);
+    assert_eq!(
+        vec![format!(
+            "bgapi originate user/1000 &park\nJob-UUID: {}",
+            job_uuid
+        )],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn send_recv_raw() -> Result<()> {
+    const STATUS_REPLY: &str = "Content-Type: api/response\nContent-Length: 7\n\n+OK up\n";
+    const UUID_REPLY: &str = "Content-Type: command/reply\nReply-Text: +OK\nJob-UUID: 7f4d\n\n";
+    let handler = |command: &str| match command {
+        "api status" => Some(vec![STATUS_REPLY.to_string()]),
+        "bgapi status" => Some(vec![UUID_REPLY.to_string()]),
+        _ => None,
+    };
+    let (stream, _mock) = common::inbound_stream(handler).await?;
+    let config = EslConfig::new().retain_raw_frames(true);
+    let inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+    assert_eq!(
+        STATUS_REPLY.as_bytes(),
+        inbound.send_recv_raw(b"api status").await?
+    );
+    assert_eq!(
+        UUID_REPLY.as_bytes(),
+        inbound.send_recv_raw(b"bgapi status").await?
+    );
+    assert_eq!("up", inbound.api("status").await?);
+
+    let (inbound, _mock) = common::inbound(handler).await?;
+    assert!(matches!(
+        inbound.send_recv_raw(b"api status").await,
+        Err(EslError::InvalidArgument(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn connection_stats() -> Result<()> {
+    let (inbound, mock) = common::inbound(|_| None).await?;
+    let setup = inbound.stats().await;
+    // auth and the event subscription
+    assert_eq!(2, setup.commands_sent);
+    assert_eq!(0, setup.events_received);
+
+    let mut events = Box::pin(inbound.events());
+    inbound.send_recv(b"log 7").await?;
+    inbound.send_recv(b"nolog").await?;
+    inbound.send_recv(b"noevents").await?;
+    mock.push(common::event_json(
+        serde_json::json!({"Event-Name": "HEARTBEAT"}),
+    ));
+    events.next().await;
+    let stats = inbound.stats().await;
+    assert_eq!(5, stats.commands_sent);
+    assert_eq!(1, stats.events_received);
+    assert_eq!(0, stats.pending_commands);
+    assert_eq!(0, stats.pending_jobs);
+    assert!(stats.last_activity > setup.last_activity);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn hold_is_idempotent() -> Result<()> {
+    let holds = Arc::new(AtomicUsize::new(0));
+    let counted = Arc::clone(&holds);
+    let (inbound, mock) = common::inbound(move |command| match command {
+        // freeswitch refuses to hold a channel which already is
+        "api uuid_hold call" => Some(vec![match counted.fetch_add(1, Ordering::SeqCst) {
+            0 => common::api_response("+OK Success\n"),
+            _ => common::api_response("-ERR Operation failed\n"),
+        }]),
+        "api uuid_hold off call" => Some(vec![common::api_response("+OK Success\n")]),
+        // held by another client, or not held at all
+        "api uuid_hold off elsewhere" => Some(vec![common::api_response("+OK Success\n")]),
+        "api uuid_hold off never" => Some(vec![common::api_response("-ERR Operation failed\n")]),
+        _ => None,
+    })
+    .await?;
+    inbound.unhold("elsewhere").await?;
+    inbound.unhold("never").await?;
+    assert!(!inbound.is_held("never"));
+    inbound.unhold("never").await?;
+    inbound.hold("call").await?;
+    inbound.hold("call").await?;
+    assert!(inbound.is_held("call"));
+    inbound.unhold("call").await?;
+    inbound.unhold("call").await?;
+    assert!(!inbound.is_held("call"));
+
+    // a hold made elsewhere is known from its event
+    let mut events = Box::pin(inbound.events());
+    mock.push(common::event_json(serde_json::json!({
+        "Event-Name": "CHANNEL_HOLD",
+        "Unique-ID": "other",
+    })));
+    events.next().await;
+    assert!(inbound.is_held("other"));
+    inbound.hold("other").await?;
+    assert_eq!(
+        vec![
+            "api uuid_hold off elsewhere",
+            "api uuid_hold off never",
+            "api uuid_hold call",
+            "api uuid_hold off call"
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn execute_without_call() -> Result<()> {
+    let (inbound, mock) = common::inbound(|_| None).await?;
+    assert_eq!(
+        Err(EslError::NotReady),
+        inbound.execute("playback", "ivr/ivr-welcome.wav").await
+    );
+    assert_eq!(Err(EslError::NotReady), inbound.answer().await);
+    assert_eq!(0, inbound.stats().await.pending_jobs);
+    assert!(mock.executed_apps().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn heartbeat_watchdog() -> Result<()> {
+    let (stream, mock) = common::inbound_stream(|_| None).await?;
+    let config = EslConfig::new().heartbeat_interval(Duration::from_millis(100));
+    let inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+    inbound.subscribe(vec!["HEARTBEAT"]).await?;
+    for _ in 0..5 {
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        mock.push(common::event_json(
+            serde_json::json!({"Event-Name": "HEARTBEAT"}),
+        ));
+    }
+    assert!(inbound.connected());
+
+    // the heartbeats stop, but the socket stays open
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    assert!(!inbound.connected());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn reconnect_restores_event_state() -> Result<()> {
+    let (inbound, mock) = common::inbound(|_| None).await?;
+    inbound.subscribe(vec!["CHANNEL_HANGUP", "DTMF"]).await?;
+    inbound.filter("Unique-ID", "call").await?;
+    inbound.filter("Unique-ID", "other").await?;
+    inbound.filter_delete("Unique-ID", "other").await?;
+    inbound.myevents("call").await?;
+    let state = inbound.event_state();
+    assert_eq!(
+        EventState {
+            events: vec![
+                "BACKGROUND_JOB".into(),
+                "CHANNEL_EXECUTE_COMPLETE".into(),
+                "CHANNEL_HANGUP".into(),
+                "DTMF".into()
+            ],
+            filters: vec![("Unique-ID".into(), "call".into())],
+            myevents: Some("call".into()),
+        },
+        state
+    );
+    // the socket is lost
+    drop(mock);
+    drop(inbound);
+
+    let (stream, mock) = common::inbound_stream(|_| None).await?;
+    let inbound = Esl::reconnect(stream, "ClueCon", EslConfig::default(), &state).await?;
+    assert_eq!(
+        vec![
+            "event json BACKGROUND_JOB CHANNEL_EXECUTE_COMPLETE CHANNEL_HANGUP DTMF",
+            "filter Unique-ID call",
+            "myevents call"
+        ],
+        mock.commands()
+    );
+    assert_eq!(state, inbound.event_state());
+    let mut events = Box::pin(inbound.events());
+    mock.push(common::event_json(serde_json::json!({
+        "Event-Name": "CHANNEL_HANGUP",
+        "Unique-ID": "call",
+    })));
+    assert_eq!(
+        Some("CHANNEL_HANGUP".into()),
+        events.next().await.unwrap().event_name()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn reconnect_restores_every_subscribe() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|_| None).await?;
+    inbound.subscribe(["CHANNEL_HANGUP"]).await?;
+    inbound.subscribe(["DTMF"]).await?;
+    inbound.subscribe_custom(&["sofia::register"]).await?;
+    let state = inbound.event_state();
+
+    let (stream, mock) = common::inbound_stream(|_| None).await?;
+    let inbound = Esl::reconnect(stream, "ClueCon", EslConfig::default(), &state).await?;
+    assert_eq!(
+        vec!["event json BACKGROUND_JOB CHANNEL_EXECUTE_COMPLETE CHANNEL_HANGUP DTMF CUSTOM sofia::register"],
+        mock.commands()
+    );
+    assert_eq!(state, inbound.event_state());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn reconnect_with_any_auth() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|_| None).await?;
+    inbound.subscribe(["DTMF"]).await?;
+    inbound.filter("Unique-ID", "call").await?;
+    let state = inbound.event_state();
+    let restored = vec![
+        "event json BACKGROUND_JOB CHANNEL_EXECUTE_COMPLETE DTMF",
+        "filter Unique-ID call",
+    ];
+
+    let (stream, mock) = common::inbound_stream(|command| {
+        command
+            .starts_with("userauth ")
+            .then(|| vec![common::command_reply("+OK accepted")])
+    })
+    .await?;
+    let auth = UserAuth(Some("1000@default:secret"));
+    let inbound = Esl::reconnect_with_auth(stream, auth, EslConfig::new(), &state).await?;
+    assert_eq!("userauth 1000@default:secret", mock.received()[0]);
+    assert_eq!(restored, mock.commands()[1..]);
+    assert_eq!(state, inbound.event_state());
+
+    // a stream authenticated elsewhere is restored on the connection itself
+    let (mut stream, mock) = common::inbound_stream(|_| None).await?;
+    let mut auth_request = vec![0; b"Content-Type: auth/request\n\n".len()];
+    stream.read_exact(&mut auth_request).await?;
+    let inbound =
+        Esl::inbound_preauthed(Preauthenticated::assume(stream), EslConfig::new()).await?;
+    inbound.restore_event_state(&state).await?;
+    assert_eq!(restored, mock.commands());
+    assert_eq!(state, inbound.event_state());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn api_bytes_keeps_invalid_utf8() -> Result<()> {
+    let (inbound, mock) =
+        common::inbound(|command| command.starts_with("api base64_decode").then(Vec::new)).await?;
+    let body = [0x52, 0x49, 0x46, 0x46, 0xff, 0xfe, 0x00, 0x80];
+    let mut frame = format!(
+        "Content-Type: api/response\nContent-Length: {}\n\n",
+        body.len()
+    )
+    .into_bytes();
+    frame.extend_from_slice(&body);
+    let (response, _) = tokio::join!(inbound.api_bytes("base64_decode UklGRv/+AIA="), async {
+        while mock.commands().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        mock.push_bytes(frame);
+    });
+    assert_eq!(Ok(body.to_vec()), response);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn strict_events() -> Result<()> {
+    let (stream, mock) = common::inbound_stream(|_| None).await?;
+    let config = EslConfig::new().strict_events(true);
+    let inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+    inbound
+        .subscribe(vec!["CHANNEL_HANGUP", "DTMF", "CUSTOM", "sofia::register"])
+        .await?;
+    assert_eq!(
+        Err(EslError::UnknownEvent("CHANNEL_HANGUPP".into())),
+        inbound.subscribe(vec!["DTMF", "CHANNEL_HANGUPP"]).await
+    );
+    assert_eq!(
+        vec!["event json CHANNEL_HANGUP DTMF CUSTOM sofia::register"],
+        mock.commands()
+    );
+    assert_eq!(
+        vec![
+            "BACKGROUND_JOB",
+            "CHANNEL_EXECUTE_COMPLETE",
+            "CHANNEL_HANGUP",
+            "DTMF",
+            "CUSTOM",
+            "sofia::register"
+        ],
+        inbound.subscribed_events()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn subscribe_mixed_custom() -> Result<()> {
+    let (inbound, mock) = common::inbound(|_| None).await?;
+    inbound
+        .subscribe([
+            EventSubscription::Custom("sofia::register".into()),
+            EventSubscription::Event("CHANNEL_HANGUP".into()),
+            EventSubscription::Custom("conference::maintenance".into()),
+            "DTMF".into(),
+        ])
+        .await?;
+    assert_eq!(
+        vec!["event json CHANNEL_HANGUP DTMF CUSTOM sofia::register conference::maintenance"],
+        mock.commands()
+    );
+    assert_eq!(
+        vec![
+            "BACKGROUND_JOB",
+            "CHANNEL_EXECUTE_COMPLETE",
+            "CHANNEL_HANGUP",
+            "DTMF",
+            "CUSTOM",
+            "sofia::register",
+            "conference::maintenance"
+        ],
+        inbound.subscribed_events()
+    );
+    Ok(())
+}
+
+/// `uuid_dump <uuid> json` captured from freeswitch, shortened
+const UUID_DUMP: &str = r##"{"Event-Name":"CHANNEL_DATA","Core-UUID":"bd0e8916-6a60-4e11-8978-db8580b440a6","FreeSWITCH-Hostname":"ip-172-31-32-63","Channel-State":"CS_EXECUTE","Channel-Call-State":"ACTIVE","Unique-ID":"c1a1b2c3-0000-4000-8000-000000000001","Call-Direction":"inbound","Answer-State":"answered","Caller-Caller-ID-Number":"1000","variable_direction":"inbound","variable_uuid":"c1a1b2c3-0000-4000-8000-000000000001","variable_sip_from_user":"1000","variable_sip_to_host":"172.31.32.63","variable_read_codec":"PCMU","variable_playback_terminators":"#","variable_current_application":"park"}"##;
+
+#[tokio::test]
+#[timeout(10000)]
+async fn uuid_getvars() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| {
+        (command == format!("api uuid_dump {} json", common::CALL_UUID))
+            .then(|| vec![common::api_response(UUID_DUMP)])
+    })
+    .await?;
+    let vars = inbound
+        .uuid_getvars(
+            common::CALL_UUID,
+            &["sip_from_user", "read_codec", "origination_caller_id_name"],
+        )
+        .await?;
+    assert_eq!(3, vars.len());
+    assert_eq!(Some(&Some("1000".into())), vars.get("sip_from_user"));
+    assert_eq!(Some(&Some("PCMU".into())), vars.get("read_codec"));
+    assert_eq!(Some(&None), vars.get("origination_caller_id_name"));
+    assert_eq!(1, mock.commands().len());
+    Ok(())
+}
+
+#[derive(Default)]
+struct CountingMetrics {
+    sent: AtomicUsize,
+    replies: AtomicUsize,
+    in_flight: AtomicUsize,
+    connects: AtomicUsize,
+    decode_errors: AtomicUsize,
+}
+
+impl Metrics for CountingMetrics {
+    fn command_sent(&self) {
+        self.sent.fetch_add(1, Ordering::SeqCst);
+    }
+    fn reply_received(&self) {
+        self.replies.fetch_add(1, Ordering::SeqCst);
+    }
+    fn in_flight(&self, commands: usize) {
+        self.in_flight.store(commands, Ordering::SeqCst);
+    }
+    fn connected(&self) {
+        self.connects.fetch_add(1, Ordering::SeqCst);
+    }
+    fn decode_error(&self) {
+        self.decode_errors.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn metrics_hooks() -> Result<()> {
+    let metrics = Arc::new(CountingMetrics::default());
+    let (stream, mock) = common::inbound_stream(|command| {
+        command
+            .starts_with("api ")
+            .then(|| vec![common::api_response("+OK\n")])
+    })
+    .await?;
+    let config = EslConfig::new().metrics(Arc::clone(&metrics));
+    let inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+    for _ in 0..3 {
+        inbound.api("status").await?;
+    }
+    // auth and the event subscription are commands too
+    assert_eq!(5, metrics.sent.load(Ordering::SeqCst));
+    assert_eq!(5, metrics.replies.load(Ordering::SeqCst));
+    assert_eq!(0, metrics.in_flight.load(Ordering::SeqCst));
+    assert_eq!(1, metrics.connects.load(Ordering::SeqCst));
+
+    mock.push("Content-Type: api/response\nContent-Length: many\n\n");
+    while metrics.decode_errors.load(Ordering::SeqCst) == 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    // the decoder resynchronizes, so the connection stays usable
+    inbound.api("status").await?;
+    assert!(inbound.connected());
+    assert_eq!(1, metrics.decode_errors.load(Ordering::SeqCst));
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn uuid_deflect() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| match command {
+        "api uuid_deflect ringing sip:1001@10.0.0.5" => Some(vec![common::api_response(
+            "-ERR Cannot deflect a channel that is not answered!\n",
+        )]),
+        _ => command
+            .starts_with("api uuid_deflect")
+            .then(|| vec![common::api_response("+OK:deflect\n")]),
+    })
+    .await?;
+    let event = inbound.uuid_deflect("bridged", "sip:1001@10.0.0.5").await?;
+    assert_eq!(&Some("+OK:deflect\n".to_string()), event.body());
+    assert_eq!(
+        Err(EslError::ApiError(
+            "Cannot deflect a channel that is not answered!".into()
+        )),
+        inbound.uuid_deflect("ringing", "sip:1001@10.0.0.5").await
+    );
+    assert_eq!(
+        vec![
+            "api uuid_deflect bridged sip:1001@10.0.0.5",
+            "api uuid_deflect ringing sip:1001@10.0.0.5",
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn conference_recording() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| match command {
+        "api conference 3000 record /recordings/3000.wav" => Some(vec![common::api_response(
+            "Record file /recordings/3000.wav\n",
+        )]),
+        "api conference 3000 norecord /recordings/3000.wav" => Some(vec![common::api_response(
+            "Stopped recording file /recordings/3000.wav\n",
+        )]),
+        "api conference 4000 record /recordings/4000.wav" => {
+            Some(vec![common::api_response("Conference 4000 not found\n")])
+        }
+        _ => None,
+    })
+    .await?;
+    assert_eq!(
+        "Record file /recordings/3000.wav\n",
+        inbound
+            .conference_record("3000", "/recordings/3000.wav")
+            .await?
+    );
+    assert_eq!(
+        "Stopped recording file /recordings/3000.wav\n",
+        inbound
+            .conference_stop_record("3000", "/recordings/3000.wav")
+            .await?
+    );
+    assert_eq!(
+        Err(EslError::ApiError("Conference 4000 not found".into())),
+        inbound
+            .conference_record("4000", "/recordings/4000.wav")
+            .await
+    );
+    assert_eq!(
+        vec![
+            "api conference 3000 record /recordings/3000.wav",
+            "api conference 3000 norecord /recordings/3000.wav",
+            "api conference 4000 record /recordings/4000.wav",
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn conference_play() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| {
+        command
+            .starts_with("api conference 3000 play")
+            .then(|| vec![common::api_response("+OK\n")])
+    })
+    .await?;
+    inbound
+        .conference_play("3000", "conference/conf-alone.wav", None)
+        .await?;
+    inbound
+        .conference_play("3000", "conference/conf-muted.wav", Some("7"))
+        .await?;
+    assert_eq!(
+        vec![
+            "api conference 3000 play conference/conf-alone.wav",
+            "api conference 3000 play conference/conf-muted.wav 7",
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn loglevel() -> Result<()> {
+    let level = Arc::new(Mutex::new("DEBUG [7]".to_string()));
+    let current = Arc::clone(&level);
+    let (inbound, mock) = common::inbound(move |command| {
+        let mut current = current.lock().unwrap();
+        match command.strip_prefix("api fsctl loglevel") {
+            Some("") => Some(vec![common::api_response(&format!(
+                "+OK log level: {}\n",
+                current
+            ))]),
+            Some(" warning") => {
+                *current = "WARNING [4]".into();
+                Some(vec![common::api_response("+OK log level: WARNING [4]\n")])
+            }
+            _ => None,
+        }
+    })
+    .await?;
+    assert_eq!(LogLevel::Debug, inbound.get_loglevel().await?);
+    inbound.set_loglevel(LogLevel::Warning).await?;
+    assert_eq!(LogLevel::Warning, inbound.get_loglevel().await?);
+    assert_eq!(
+        vec![
+            "api fsctl loglevel",
+            "api fsctl loglevel warning",
+            "api fsctl loglevel",
+        ],
+        mock.commands()
+    );
+    *level.lock().unwrap() = "CRIT".into();
+    assert_eq!(LogLevel::Crit, inbound.get_loglevel().await?);
+    assert_eq!(Ok(LogLevel::Err), "err".parse());
+    assert_eq!(Ok(LogLevel::Console), "0".parse());
+    assert!(matches!(
+        "verbose".parse::<LogLevel>(),
+        Err(EslError::InvalidArgument(_))
+    ));
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn uuid_send_info() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| {
+        command
+            .starts_with("api uuid_send_info")
+            .then(|| vec![common::api_response("+OK\n")])
+    })
+    .await?;
+    let event = inbound
+        .uuid_send_info("call", "application/dtmf-relay", "Signal=5 Duration=160")
+        .await?;
+    assert_eq!(&Some("+OK\n".to_string()), event.body());
+    assert_eq!(
+        vec!["api uuid_send_info call application dtmf-relay Signal=5 Duration=160"],
+        mock.commands()
+    );
+    for (content_type, body) in [
+        ("text", "hello"),
+        ("application/dtmf-relay", "Signal=5\r\nDuration=160"),
+    ] {
+        assert!(matches!(
+            inbound.uuid_send_info("call", content_type, body).await,
+            Err(EslError::InvalidArgument(_))
+        ));
+    }
+    assert_eq!(1, mock.commands().len());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn uuid_chat() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| match command {
+        "api uuid_chat gone hi" => Some(vec![common::api_response("-ERR No such channel!\n")]),
+        _ => command
+            .starts_with("api uuid_chat")
+            .then(|| vec![common::api_response("+OK\n")]),
+    })
+    .await?;
+    let event = inbound
+        .uuid_chat("call", "Your agent is Alice, ETA: 2 min")
+        .await?;
+    assert_eq!(&Some("+OK\n".to_string()), event.body());
+    assert_eq!(
+        Err(EslError::ApiError("No such channel!".into())),
+        inbound.uuid_chat("gone", "hi").await.map(|_| ())
+    );
+    for body in ["", " ", "line1\nline2"] {
+        assert!(matches!(
+            inbound.uuid_chat("call", body).await,
+            Err(EslError::InvalidArgument(_))
+        ));
+    }
+    assert_eq!(
+        vec![
+            "api uuid_chat call Your agent is Alice, ETA: 2 min",
+            "api uuid_chat gone hi",
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn uuid_early_media_control() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| match command {
+        "api uuid_ring_ready call" | "api uuid_pre_answer call" => {
+            Some(vec![common::api_response("+OK\n")])
+        }
+        "api uuid_pre_answer gone" => Some(vec![common::api_response("-ERR No such channel!\n")]),
+        _ => None,
+    })
+    .await?;
+    inbound.uuid_ring_ready("call").await?;
+    inbound.uuid_pre_answer("call").await?;
+    assert!(matches!(
+        inbound.uuid_pre_answer("gone").await,
+        Err(EslError::ApiError(_))
+    ));
+    assert_eq!(
+        vec![
+            "api uuid_ring_ready call",
+            "api uuid_pre_answer call",
+            "api uuid_pre_answer gone"
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn uuid_phone_event() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| match command {
+        "api uuid_phone_event gone hold" => {
+            Some(vec![common::api_response("-ERR No such channel!\n")])
+        }
+        _ => command
+            .starts_with("api uuid_phone_event")
+            .then(|| vec![common::api_response("+OK\n")]),
+    })
+    .await?;
+    inbound.uuid_phone_event("call", PhoneEvent::Talk).await?;
+    inbound.uuid_phone_event("call", PhoneEvent::Hold).await?;
+    assert_eq!(
+        Err(EslError::ApiError("No such channel!".into())),
+        inbound.uuid_phone_event("gone", PhoneEvent::Hold).await
+    );
+    assert_eq!(
+        vec![
+            "api uuid_phone_event call talk",
+            "api uuid_phone_event call hold",
+            "api uuid_phone_event gone hold",
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn uuid_jitterbuffer() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| {
+        command
+            .starts_with("api uuid_")
+            .then(|| vec![common::api_response("+OK\n")])
+    })
+    .await?;
+    inbound.uuid_jitterbuffer("call", "60").await?;
+    inbound.uuid_jitterbuffer("call", "60:200:20").await?;
+    inbound.uuid_jitterbuffer("call", "off").await?;
+    inbound.uuid_set_media_stats("call").await?;
+    for spec in ["", "60ms", "60:200:20:5", "on"] {
+        assert!(matches!(
+            inbound.uuid_jitterbuffer("call", spec).await,
+            Err(EslError::InvalidArgument(_))
+        ));
+    }
+    assert_eq!(
+        vec![
+            "api uuid_jitterbuffer call 60",
+            "api uuid_jitterbuffer call 60:200:20",
+            "api uuid_jitterbuffer call off",
+            "api uuid_set_media_stats call",
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn eval_expression() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| match command {
+        "api eval ${sofia_contact(1000@${domain})}" => Some(vec![common::api_response(
+            "sofia/internal/sip:1000@192.168.1.20:5060;transport=udp",
+        )]),
+        "api eval ${undefined_var}" => Some(vec![common::api_response("")]),
+        _ => None,
+    })
+    .await?;
+    assert_eq!(
+        "sofia/internal/sip:1000@192.168.1.20:5060;transport=udp",
+        inbound.eval("${sofia_contact(1000@${domain})}").await?
+    );
+    assert_eq!("", inbound.eval("${undefined_var}").await?);
+    assert_eq!(
+        vec![
+            "api eval ${sofia_contact(1000@${domain})}",
+            "api eval ${undefined_var}",
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+const RTP_DUMP: &str = r##"{"Event-Name":"CHANNEL_DATA","Unique-ID":"c1a1b2c3-0000-4000-8000-000000000001","Answer-State":"answered","variable_read_codec":"PCMU","variable_rtp_audio_in_raw_bytes":"396112","variable_rtp_audio_in_media_bytes":"394392","variable_rtp_audio_in_packet_count":"2303","variable_rtp_audio_in_skip_packet_count":"47","variable_rtp_audio_in_jitter_min_variance":"1.52","variable_rtp_audio_in_jitter_max_variance":"23.75","variable_rtp_audio_in_jitter_loss_rate":"0.00","variable_rtp_audio_in_jitter_burst_rate":"0.00","variable_rtp_audio_in_mean_interval":"20.03","variable_rtp_audio_in_quality_percentage":"98.00","variable_rtp_audio_in_mos":"4.41","variable_rtp_audio_in_rtcp_rtt":"38","variable_rtp_audio_out_raw_bytes":"396800","variable_rtp_audio_out_packet_count":"2307","variable_rtp_audio_out_skip_packet_count":"0","variable_rtp_audio_out_jitter_max_variance":"not measured"}"##;
+
+#[tokio::test]
+#[timeout(10000)]
+async fn media_stats() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| match command {
+        "api uuid_set_media_stats call" => Some(vec![common::api_response("+OK\n")]),
+        "api uuid_dump call json" => Some(vec![common::api_response(RTP_DUMP)]),
+        _ => None,
+    })
+    .await?;
+    let stats = inbound.media_stats("call").await?;
+    assert_eq!(Some(4.41), stats.inbound.mos);
+    assert_eq!(Some(23.75), stats.inbound.jitter_ms);
+    assert_eq!(Some(2.0), stats.inbound.packet_loss_pct);
+    assert_eq!(Some(38.0), stats.inbound.rtt_ms);
+    assert_eq!(
+        MediaDirectionStats {
+            mos: None,
+            jitter_ms: None,
+            packet_loss_pct: Some(0.0),
+            rtt_ms: None,
+        },
+        stats.outbound
+    );
+    assert_eq!(
+        vec!["api uuid_set_media_stats call", "api uuid_dump call json"],
+        mock.commands()
+    );
+    Ok(())
+}
+
+const TIMES_DUMP: &str = r##"{"Event-Name":"CHANNEL_DATA","Unique-ID":"c1a1b2c3-0000-4000-8000-000000000001","Channel-State":"CS_EXECUTE","Answer-State":"ringing","Caller-Direction":"inbound","Caller-Profile-Created-Time":"1696412012511372","Caller-Channel-Created-Time":"1696412012511372","Caller-Channel-Answered-Time":"0","Caller-Channel-Progress-Time":"0","Caller-Channel-Progress-Media-Time":"1696412012731990","Caller-Channel-Hangup-Time":"0","Caller-Channel-Transfer-Time":"0"}"##;
+
+#[tokio::test]
+#[timeout(10000)]
+async fn call_timestamps() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| match command {
+        "api uuid_dump call json" => Some(vec![common::api_response(TIMES_DUMP)]),
+        _ => None,
+    })
+    .await?;
+    let times = inbound.call_timestamps("call").await?;
+    assert_eq!(
+        CallTimestamps {
+            created: Some(UNIX_EPOCH + Duration::from_micros(1696412012511372)),
+            answered: None,
+            hangup: None,
+        },
+        times
+    );
+    assert_eq!(vec!["api uuid_dump call json"], mock.commands());
+    Ok(())
+}
+
+const REGISTRATIONS_JSON: &str = r#"{"row_count":2,"rows":[{"reg_user":"1000","realm":"pbx.example.com","token":"MjY1ZmI0","url":"sofia/internal/sip:1000@10.0.0.5:5060;rinstance=2c5a","expires":"1696415612","network_ip":"10.0.0.5","network_port":"5060","network_proto":"udp","hostname":"fs1","metadata":""},{"reg_user":"1001","realm":"pbx.example.com","token":"ZDk2NzAx","url":"sofia/internal/sip:1001@10.0.0.6:5061;transport=tls","expires":"1696415700","network_ip":"10.0.0.6","network_port":"5061","network_proto":"tls","hostname":"fs1","metadata":""}]}"#;
+
+#[tokio::test]
+#[timeout(10000)]
+async fn show_registrations_json() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| match command {
+        "api show registrations as json" => Some(vec![common::api_response(REGISTRATIONS_JSON)]),
+        _ => None,
+    })
+    .await?;
+    let registrations = inbound.show_registrations().await?;
+    assert_eq!(2, registrations.len());
+    assert_eq!(
+        ShowRegistration {
+            user: Some("1000".into()),
+            realm: Some("pbx.example.com".into()),
+            url: Some("sofia/internal/sip:1000@10.0.0.5:5060;rinstance=2c5a".into()),
+            expires: Some(1696415612),
+            network_ip: Some("10.0.0.5".into()),
+            network_port: Some("5060".into()),
+            network_proto: Some("udp".into()),
+            hostname: Some("fs1".into()),
+        },
+        registrations[0]
+    );
+    assert_eq!(Some("tls".into()), registrations[1].network_proto);
+    assert_eq!(vec!["api show registrations as json"], mock.commands());
+    Ok(())
+}
+
+/// `show calls` of a build without json support, the dialplan data contains commas
+const CALLS_CSV: &str = "uuid,direction,created,created_epoch,name,state,cid_name,cid_num,ip_addr,dest,callstate,b_uuid,application_data
+a1b2c3d4-0000-4000-8000-000000000001,inbound,2023-10-04 09:33:32,1696412012,sofia/internal/1000@10.0.0.5:5060,CS_EXCHANGE_MEDIA,Alice,1000,10.0.0.5,2000,ACTIVE,b2c3d4e5-0000-4000-8000-000000000002,user/2000,user/2001
+c3d4e5f6-0000-4000-8000-000000000003,outbound,2023-10-04 09:34:10,1696412050,sofia/external/+15551230000,CS_EXECUTE,,,,+15551230000,RINGING,,
+
+2 total.
+";
+
+#[tokio::test]
+#[timeout(10000)]
+async fn show_calls_csv() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|command| match command {
+        "api show calls as json" => Some(vec![common::api_response(CALLS_CSV)]),
+        _ => None,
+    })
+    .await?;
+    let calls = inbound.show_calls().await?;
+    assert_eq!(
+        vec![
+            ShowCall {
+                uuid: Some("a1b2c3d4-0000-4000-8000-000000000001".into()),
+                direction: Some("inbound".into()),
+                created_epoch: Some(1696412012),
+                name: Some("sofia/internal/1000@10.0.0.5:5060".into()),
+                cid_num: Some("1000".into()),
+                dest: Some("2000".into()),
+                callstate: Some("ACTIVE".into()),
+                b_uuid: Some("b2c3d4e5-0000-4000-8000-000000000002".into()),
+            },
+            ShowCall {
+                uuid: Some("c3d4e5f6-0000-4000-8000-000000000003".into()),
+                direction: Some("outbound".into()),
+                created_epoch: Some(1696412050),
+                name: Some("sofia/external/+15551230000".into()),
+                cid_num: None,
+                dest: Some("+15551230000".into()),
+                callstate: Some("RINGING".into()),
+                b_uuid: None,
+            },
+        ],
+        calls
+    );
+    Ok(())
+}
+
+/// Answers `bgapi originate` with its job, which reports `result` of the leg
+fn originate_job(command: &str, result: &str) -> Vec<String> {
+    let job_uuid = common::command_header(command, "Job-UUID").unwrap_or_default();
+    vec![
+        common::command_reply(&format!("+OK Job-UUID: {}", job_uuid)),
+        common::event_json(serde_json::json!({
+            "Event-Name": "BACKGROUND_JOB",
+            "Job-UUID": job_uuid,
+            "_body": result.replace("UUID", job_uuid),
+        })),
+    ]
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn originate_and_bridge() -> Result<()> {
+    let originate = Regex::new(
+        r"^bgapi originate \{origination_uuid=([0-9a-f-]{36})\}(\S+) &park\(\)\nJob-UUID: ([0-9a-f-]{36})$",
+    )?;
+    let (inbound, mock) = common::inbound(move |command| {
+        if let Some(captures) = originate.captures(command) {
+            assert_eq!(&captures[1], &captures[3]);
+            return Some(match &captures[2] {
+                "user/1003" => originate_job(command, "-ERR NO_ANSWER\n"),
+                _ => originate_job(command, "+OK UUID\n"),
+            });
+        }
+        let reply = match command.split(' ').nth(1)? {
+            "uuid_bridge" if command.ends_with(" gone") => "-ERR no such channel\n",
+            "uuid_bridge" | "uuid_kill" => "+OK\n",
+            _ => return None,
+        };
+        Some(vec![common::api_response(reply)])
+    })
+    .await?;
+    let leg = inbound.originate_and_bridge("user/1002", "call").await?;
+    assert_eq!(
+        Err(EslError::ApiError("NO_ANSWER".into())),
+        inbound.originate_and_bridge("user/1003", "call").await
+    );
+    assert_eq!(
+        Err(EslError::ApiError("no such channel".into())),
+        inbound.originate_and_bridge("user/1004", "gone").await
+    );
+    let commands = mock.commands();
+    assert_eq!(7, commands.len());
+    assert_eq!(
+        format!(
+            "bgapi originate {{origination_uuid={}}}user/1002 &park()\nJob-UUID: {}",
+            leg, leg
+        ),
+        commands[0]
+    );
+    assert_eq!(format!("api uuid_bridge {} call", leg), commands[1]);
+    let unanswered = &commands[2]["bgapi originate {origination_uuid=".len()..][..36];
+    assert!(commands[2].contains("}user/1003 &park()"));
+    assert_eq!(format!("api uuid_kill {}", unanswered), commands[3]);
+    let orphan = &commands[4]["bgapi originate {origination_uuid=".len()..][..36];
+    assert_eq!(format!("api uuid_bridge {} gone", orphan), commands[5]);
+    assert_eq!(format!("api uuid_kill {}", orphan), commands[6]);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn originate_and_bridge_timeout_kills_leg() -> Result<()> {
+    let (stream, mock) = common::inbound_stream(|command| {
+        if command.starts_with("bgapi originate ") {
+            // the far end keeps ringing, so the job never completes
+            let job_uuid = common::command_header(command, "Job-UUID").unwrap_or_default();
+            let reply = format!("+OK Job-UUID: {}", job_uuid);
+            return Some(vec![common::command_reply(&reply)]);
+        }
+        command
+            .starts_with("api uuid_kill ")
+            .then(|| vec![common::api_response("+OK\n")])
+    })
+    .await?;
+    let config = EslConfig::new().bgapi_timeout(Duration::from_millis(50));
+    let inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+    let result = inbound.originate_and_bridge("user/1002", "call").await;
+    assert!(matches!(result, Err(EslError::Timeout { .. })));
+    let commands = mock.commands();
+    assert_eq!(2, commands.len());
+    let leg = &commands[0]["bgapi originate {origination_uuid=".len()..][..36];
+    assert_eq!(format!("api uuid_kill {}", leg), commands[1]);
+    assert_eq!(0, inbound.stats().await.pending_jobs);
+    Ok(())
+}
+
+const STATUS_1_10: &str =
+    "UP 0 years, 3 days, 4 hours, 12 minutes, 9 seconds, 312 milliseconds, 77 microseconds
+FreeSWITCH (Version 1.10.9-release git 2a7be6e 2023-03-06 64bit) is ready
+1532 session(s) since startup
+7 session(s) - peak 42, last 5min 11
+2 session(s) per Sec out of max 30, peak 9, last 5min 3
+1000 session(s) max
+min idle cpu 0.00/96.53
+Current Stack Size/Max 240K/8192K
+";
+
+#[tokio::test]
+#[timeout(10000)]
+async fn core_status() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|command| {
+        (command == "api status").then(|| vec![common::api_response(STATUS_1_10)])
+    })
+    .await?;
+    assert_eq!(
+        CoreStatus {
+            sessions_since_startup: Some(1532),
+            sessions: Some(7),
+            peak_sessions: Some(42),
+            sessions_per_sec: Some(2),
+            max_sessions_per_sec: Some(30),
+            max_sessions: Some(1000),
+            idle_cpu: Some(96.53),
+        },
+        inbound.core_status().await?
+    );
+
+    // 1.2 reports the rate as a fraction and no peak
+    let status_1_2 =
+        "UP 0 years, 0 days, 0 hours, 5 minutes, 1 second, 10 milliseconds, 2 microseconds
+12 session(s) since startup
+3 session(s) 1/30
+1000 session(s) max
+min idle cpu 0.00/100.00
+";
+    assert_eq!(
+        CoreStatus {
+            sessions_since_startup: Some(12),
+            sessions: Some(3),
+            peak_sessions: None,
+            sessions_per_sec: Some(1),
+            max_sessions_per_sec: Some(30),
+            max_sessions: Some(1000),
+            idle_cpu: Some(100.0),
+        },
+        CoreStatus::from(status_1_2)
+    );
+
+    // reordered lines without the session limit and cpu
+    let trimmed = "0 session(s) - peak 0, last 5min 0
+0 session(s) per Sec out of max 200, peak 0, last 5min 0
+FreeSWITCH (Version 1.6.20 git 987c9b9 2018-01-23 19:31:29Z 64bit) is ready
+4 session(s) since startup
+UP 0 years, 0 days, 0 hours, 0 minutes, 42 seconds, 1 millisecond, 5 microseconds
+";
+    assert_eq!(
+        CoreStatus {
+            sessions_since_startup: Some(4),
+            sessions: Some(0),
+            peak_sessions: Some(0),
+            sessions_per_sec: Some(0),
+            max_sessions_per_sec: Some(200),
+            max_sessions: None,
+            idle_cpu: None,
+        },
+        CoreStatus::from(trimmed)
+    );
+    assert_eq!(CoreStatus::default(), CoreStatus::from("UP 0 years"));
+    Ok(())
+}
+
+fn conference_event(action: &str, member_id: &str, uuid: &str) -> String {
+    common::event_json(serde_json::json!({
+        "Event-Name": "CUSTOM",
+        "Core-UUID": "bd0e8916-6a60-4e11-8978-db8580b440a6",
+        "Event-Subclass": "conference::maintenance",
+        "Event-Date-Timestamp": "1694493097638660",
+        "Conference-Name": "3000",
+        "Conference-Size": "2",
+        "Conference-Profile-Name": "default",
+        "Unique-ID": uuid,
+        "Caller-Caller-ID-Number": "1000",
+        "Member-ID": member_id,
+        "Member-Type": "member",
+        "Action": action,
+    }))
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn conference_events() -> Result<()> {
+    let (inbound, mock) = common::inbound(|_| None).await?;
+    inbound
+        .subscribe_custom(&["conference::maintenance"])
+        .await?;
+    assert_eq!(
+        vec!["event json CUSTOM conference::maintenance"],
+        mock.commands()
+    );
+    let events = inbound.events_filtered(&["CUSTOM"]);
+    let mut events = Box::pin(events);
+    let actions = [
+        "add-member",
+        "start-talking",
+        "stop-talking",
+        "mute-member",
+        "unmute-member",
+        "del-member",
+        "energy-level",
+    ];
+    for action in actions {
+        mock.push(conference_event(action, "7", common::CALL_UUID));
+    }
+    let member = ConferenceMember {
+        conference: Some("3000".into()),
+        member_id: Some("7".into()),
+        uuid: Some(common::CALL_UUID.into()),
+        caller_id_number: Some("1000".into()),
+    };
+    let expected = [
+        ConferenceEvent::MemberJoin(member.clone()),
+        ConferenceEvent::StartTalking(member.clone()),
+        ConferenceEvent::StopTalking(member.clone()),
+        ConferenceEvent::Mute(member.clone()),
+        ConferenceEvent::Unmute(member.clone()),
+        ConferenceEvent::MemberLeave(member),
+    ];
+    for expected in expected {
+        let event = events.next().await.unwrap();
+        assert_eq!(Ok(expected), ConferenceEvent::try_from(&event));
+    }
+    let event = events.next().await.unwrap();
+    assert_eq!(
+        Err(EslError::UnexpectedEvent(
+            "conference::maintenance energy-level".into()
+        )),
+        ConferenceEvent::try_from(&event)
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn registration_events() -> Result<()> {
+    let (inbound, mock) = common::inbound(|_| None).await?;
+    inbound
+        .subscribe_custom(&["sofia::register", "sofia::unregister", "sofia::expire"])
+        .await?;
+    let mut events = Box::pin(inbound.events_filtered(&["CUSTOM"]));
+    mock.push(common::event_json(serde_json::json!({
+        "Event-Name": "CUSTOM",
+        "Event-Subclass": "sofia::register",
+        "profile-name": "internal",
+        "from-user": "1000",
+        "from-host": "172.31.32.63",
+        "contact": "\"user\" <sip:1000@10.0.0.5:5060;rinstance=2c5a>",
+        "call-id": "MjY1ZmI0",
+        "expires": "3600",
+        "to-user": "1000",
+        "to-host": "172.31.32.63",
+        "network-ip": "10.0.0.5",
+        "network-port": "5060",
+        "username": "1000",
+        "realm": "172.31.32.63",
+        "user-agent": "Zoiper rv2.10",
+    })));
+    mock.push(common::event_json(serde_json::json!({
+        "Event-Name": "CUSTOM",
+        "Event-Subclass": "sofia::expire",
+        "profile-name": "internal",
+        "call-id": "MjY1ZmI0",
+        "user": "1000",
+        "host": "172.31.32.63",
+        "contact": "\"user\" <sip:1000@10.0.0.5:5060;rinstance=2c5a>",
+        "expires": "3600",
+        "user-agent": "Zoiper rv2.10",
+    })));
+    let register = RegistrationEvent::try_from(&events.next().await.unwrap())?;
+    assert_eq!(
+        RegistrationEvent {
+            user: Some("1000".into()),
+            realm: Some("172.31.32.63".into()),
+            contact: Some("\"user\" <sip:1000@10.0.0.5:5060;rinstance=2c5a>".into()),
+            network_ip: Some("10.0.0.5".into()),
+            action: RegistrationAction::Register,
+        },
+        register
+    );
+    let expire = RegistrationEvent::try_from(&events.next().await.unwrap())?;
+    assert_eq!(RegistrationAction::Expire, expire.action);
+    assert_eq!(register.user, expire.user);
+    assert_eq!(register.realm, expire.realm);
+    assert_eq!(register.contact, expire.contact);
+    assert_eq!(None, expire.network_ip);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn channel_lifecycle_events() -> Result<()> {
+    let (inbound, mock) = common::inbound(|_| None).await?;
+    let mut events = Box::pin(inbound.events_filtered(&["CHANNEL_CREATE", "CHANNEL_DESTROY"]));
+    mock.push(common::event_json(serde_json::json!({
+        "Event-Name": "CHANNEL_CREATE",
+        "Core-UUID": "6d7ff4a3-2d6e-4a6f-9f4d-3bb0a0e1e6c2",
+        "Event-Date-Timestamp": "1696412012531372",
+        "Channel-State": "CS_INIT",
+        "Channel-Call-State": "DOWN",
+        "Channel-Name": "sofia/external/+15551230000@gw.example.com",
+        "Unique-ID": "b2c3d4e5-0000-4000-8000-000000000002",
+        "Call-Direction": "outbound",
+        "Answer-State": "ringing",
+        "Other-Type": "originator",
+        "Other-Leg-Unique-ID": "a1b2c3d4-0000-4000-8000-000000000001",
+    })));
+    mock.push(common::event_json(serde_json::json!({
+        "Event-Name": "CHANNEL_DESTROY",
+        "Core-UUID": "6d7ff4a3-2d6e-4a6f-9f4d-3bb0a0e1e6c2",
+        "Channel-State": "CS_DESTROY",
+        "Channel-Call-State": "HANGUP",
+        "Channel-Name": "sofia/internal/1000@10.0.0.5:5060",
+        "Unique-ID": "a1b2c3d4-0000-4000-8000-000000000001",
+        "Call-Direction": "inbound",
+        "Answer-State": "hangup",
+        "Hangup-Cause": "NORMAL_CLEARING",
+    })));
+    let create = ChannelLifecycleEvent::try_from(&events.next().await.unwrap())?;
+    assert_eq!(
+        ChannelLifecycleEvent {
+            uuid: Some("b2c3d4e5-0000-4000-8000-000000000002".into()),
+            destroyed: false,
+            direction: Some("outbound".into()),
+            channel_name: Some("sofia/external/+15551230000@gw.example.com".into()),
+            other_leg_uuid: Some("a1b2c3d4-0000-4000-8000-000000000001".into()),
+        },
+        create
+    );
+    let destroy = ChannelLifecycleEvent::try_from(&events.next().await.unwrap())?;
+    assert_eq!(
+        ChannelLifecycleEvent {
+            uuid: Some("a1b2c3d4-0000-4000-8000-000000000001".into()),
+            destroyed: true,
+            direction: Some("inbound".into()),
+            channel_name: Some("sofia/internal/1000@10.0.0.5:5060".into()),
+            other_leg_uuid: None,
+        },
+        destroy
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn bridge_events_resolve_peer() -> Result<()> {
+    const A_LEG: &str = "a1b2c3d4-0000-4000-8000-000000000001";
+    const B_LEG: &str = "b2c3d4e5-0000-4000-8000-000000000002";
+    let (inbound, mock) = common::inbound(|_| None).await?;
+    let mut events = Box::pin(inbound.events_filtered(&[
+        "CHANNEL_BRIDGE",
+        "CHANNEL_UNBRIDGE",
+        "CHANNEL_DESTROY",
+    ]));
+    mock.push(common::event_json(serde_json::json!({
+        "Event-Name": "CHANNEL_BRIDGE",
+        "Unique-ID": A_LEG,
+        "Channel-Name": "sofia/internal/1000@10.0.0.5:5060",
+        "Bridge-A-Unique-ID": A_LEG,
+        "Bridge-B-Unique-ID": B_LEG,
+        "Other-Leg-Unique-ID": B_LEG,
+    })));
+    let bridge = BridgeEvent::try_from(&events.next().await.unwrap())?;
+    assert_eq!(
+        BridgeEvent {
+            a_leg_uuid: Some(A_LEG.into()),
+            b_leg_uuid: Some(B_LEG.into()),
+            unbridged: false,
+        },
+        bridge
+    );
+    assert_eq!(Some(B_LEG.into()), inbound.bridged_peer(A_LEG));
+    assert_eq!(Some(A_LEG.into()), inbound.bridged_peer(B_LEG));
+
+    mock.push(common::event_json(serde_json::json!({
+        "Event-Name": "CHANNEL_UNBRIDGE",
+        "Unique-ID": A_LEG,
+        "Bridge-A-Unique-ID": A_LEG,
+        "Bridge-B-Unique-ID": B_LEG,
+    })));
+    assert!(BridgeEvent::try_from(&events.next().await.unwrap())?.unbridged);
+    assert_eq!(Some(B_LEG.into()), inbound.bridged_peer(A_LEG));
+
+    mock.push(common::event_json(serde_json::json!({
+        "Event-Name": "CHANNEL_DESTROY",
+        "Unique-ID": A_LEG,
+    })));
+    events.next().await;
+    assert_eq!(None, inbound.bridged_peer(A_LEG));
+    assert_eq!(None, inbound.bridged_peer("unknown"));
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn send_recv_last_drains_intermediates() -> Result<()> {
+    let metrics = Arc::new(CountingMetrics::default());
+    let (stream, _mock) = common::inbound_stream(|command| match command {
+        "linger" => Some(vec![common::command_reply("+OK will linger")]),
+        "divert_events on" => Some(vec![common::command_reply("+OK events diverted")]),
+        "api echo after" => Some(vec![common::api_response("+OK after\n")]),
+        _ => None,
+    })
+    .await?;
+    let config = EslConfig::new().metrics(Arc::clone(&metrics));
+    let inbound = Esl::inbound_with_config(stream, "ClueCon", config).await?;
+    let reply = inbound
+        .send_recv_last(&[b"linger", b"myevents", b"divert_events on"])
+        .await?;
+    assert_eq!(Some("+OK events diverted"), reply.reply_text());
+    assert_eq!(0, metrics.in_flight.load(Ordering::SeqCst));
+    assert_eq!("after", inbound.api("echo after").await?);
+    assert_eq!(
+        Err(EslError::InvalidArgument("no messages to send".into())),
+        inbound.send_recv_last(&[]).await
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn subscribe_scoped_nixevent_on_drop() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| {
+        (command == "api status").then(|| vec![common::api_response("+OK up\n")])
+    })
+    .await?;
+    inbound.subscribe(["CHANNEL_HANGUP"]).await?;
+    {
+        let guard = inbound
+            .subscribe_scoped(["CHANNEL_HANGUP", "CHANNEL_BRIDGE", "DTMF"])
+            .await?;
+        assert_eq!(["CHANNEL_BRIDGE", "DTMF"], guard.events());
+    }
+    // nixevent is sent in the background
+    while !mock
+        .commands()
+        .iter()
+        .any(|command| command.starts_with("nixevent"))
+    {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    // its discarded reply doesn't shift replies of later commands
+    assert_eq!(Ok("up".into()), inbound.api("status").await);
+    assert_eq!(
+        vec![
+            "event json CHANNEL_HANGUP",
+            "event json CHANNEL_HANGUP CHANNEL_BRIDGE DTMF",
+            "nixevent CHANNEL_BRIDGE DTMF",
+            "api status",
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn subscribe_scoped_keeps_earlier_subscriptions() -> Result<()> {
+    let (inbound, mock) = common::inbound(|_| None).await?;
+    inbound.subscribe(["CHANNEL_HANGUP"]).await?;
+    inbound.subscribe_custom(&["sofia::register"]).await?;
+    let guard = inbound
+        .subscribe_scoped([
+            EventSubscription::Event("CHANNEL_HANGUP".into()),
+            EventSubscription::Custom("sofia::register".into()),
+            EventSubscription::Custom("conference::maintenance".into()),
+        ])
+        .await?;
+    assert_eq!(["CUSTOM", "conference::maintenance"], guard.events());
+    guard.unsubscribe().await?;
+    assert_eq!(
+        Some(&"nixevent CUSTOM conference::maintenance".to_string()),
+        mock.commands().last()
+    );
+    assert_eq!(
+        vec![
+            "BACKGROUND_JOB",
+            "CHANNEL_EXECUTE_COMPLETE",
+            "CHANNEL_HANGUP",
+            "CUSTOM",
+            "sofia::register"
+        ],
+        inbound.subscribed_events()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn originate_with_origination_uuid() -> Result<()> {
+    let originate = Regex::new(r"origination_uuid=([0-9a-f-]{36})[,}]")?;
+    let (inbound, mock) = common::inbound(move |command| {
+        let captures = originate.captures(command)?;
+        Some(vec![common::api_response(&format!(
+            "+OK {}\n",
+            &captures[1]
+        ))])
+    })
+    .await?;
+    let uuid = "0c1b4b3e-8f5a-4d4c-9c1e-2f0a5b6d7e8f";
+    let options = OriginateOptions::new("user/1000", OriginateTarget::extension("5000"))
+        .variable("origination_caller_id_name", "Sales, EU")
+        .variable("origination_uuid", uuid);
+    assert_eq!(Ok(uuid.to_string()), inbound.originate(options).await);
+    let generated = inbound
+        .originate(OriginateOptions::new(
+            "{ignore_early_media=true}user/1001",
+            OriginateTarget::application("park", ""),
+        ))
+        .await?;
+    let invalid = OriginateOptions::new("user/1002", OriginateTarget::extension("5000"))
+        .origination_uuid("not-a-uuid");
+    assert!(matches!(
+        inbound.originate(invalid).await,
+        Err(EslError::InvalidArgument(_))
+    ));
+    assert_eq!(
+        vec![
+            format!(
+                "api originate {{origination_caller_id_name='Sales, EU',origination_uuid={}}}user/1000 5000",
+                uuid
+            ),
+            format!(
+                "api originate {{origination_uuid={},ignore_early_media=true}}user/1001 &park()",
+                generated
+            ),
+        ],
+        mock.commands()
+    );
+    Ok(())
+}
+
+/// Whole inbound session: auth, subscription, concurrent api and bgapi,
+/// an event and disconnect, checking what was sent and where each reply went
+#[tokio::test]
+#[timeout(10000)]
+async fn inbound_session_happy_path() -> Result<()> {
+    let (inbound, mock) = common::inbound(|command| match command {
+        "api status" => Some(vec![common::api_response("+OK UP 0 years, 1 day\n")]),
+        "api version" => Some(vec![common::api_response(
+            "+OK FreeSWITCH Version 1.10.11\n",
+        )]),
+        _ => {
+            let job_uuid = command
+                .strip_prefix("bgapi reloadxml\nJob-UUID: ")?
+                .to_string();
+            Some(vec![
+                common::command_reply(&format!("+OK Job-UUID: {}", job_uuid)),
+                common::event_json(serde_json::json!({
+                    "Event-Name": "BACKGROUND_JOB",
+                    "Job-UUID": job_uuid,
+                    "Job-Command": "reloadxml",
+                    "_body": "+OK [Success]\n",
+                })),
+            ])
+        }
+    })
+    .await?;
+    assert!(inbound.connected());
+    inbound.subscribe(["CHANNEL_ANSWER"]).await?;
+    let mut events = Box::pin(inbound.events());
+
+    let (status, version, job) = tokio::join!(
+        inbound.api("status"),
+        inbound.api("version"),
+        inbound.bgapi_with_job("reloadxml")
+    );
+    assert_eq!(Ok("UP 0 years, 1 day".into()), status);
+    assert_eq!(Ok("FreeSWITCH Version 1.10.11".into()), version);
+    let (job_uuid, job_text) = job?;
+    assert_eq!("[Success]", job_text);
+
+    mock.push(common::event_json(serde_json::json!({
+        "Event-Name": "CHANNEL_ANSWER",
+        "Unique-ID": common::CALL_UUID,
+    })));
+    // the BACKGROUND_JOB went to bgapi, so the answer is the first event
+    let event = events.next().await.unwrap();
+    assert_eq!(Some("CHANNEL_ANSWER".into()), event.event_name());
+
+    inbound.disconnect().await?;
+    assert_eq!(
+        vec![
+            "auth ClueCon".to_string(),
+            "event json BACKGROUND_JOB CHANNEL_EXECUTE_COMPLETE".into(),
+            "event json CHANNEL_ANSWER".into(),
+            "api status".into(),
+            "api version".into(),
+            format!("bgapi reloadxml\nJob-UUID: {}", job_uuid),
+            "exit".into(),
+        ],
+        mock.received()
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+#[timeout(10000)]
+async fn concurrent_api_replies_reach_their_caller() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|command| {
+        let n = command.strip_prefix("api echo ")?;
+        Some(vec![common::api_response(&format!("+OK {}\n", n))])
+    })
+    .await?;
+    let inbound = Arc::new(inbound);
+    let calls: Vec<_> = (0..200)
+        .map(|n| {
+            let inbound = Arc::clone(&inbound);
+            tokio::spawn(async move { (n, inbound.api(&format!("echo {}", n)).await) })
+        })
+        .collect();
+    for call in calls {
+        let (n, reply) = call.await?;
+        assert_eq!(Ok(n.to_string()), reply);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn api_multiline_ok_text() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|command| {
+        let body = match command {
+            "api multiline" => "+OK\nline1\nline2\n",
+            "api indented" => "+OK \n  line1\nline2\n",
+            "api failed" => "-ERR\ncall failed\n",
+            _ => return None,
+        };
+        Some(vec![common::api_response(body)])
+    })
+    .await?;
+    assert_eq!(Ok("line1\nline2".into()), inbound.api("multiline").await);
+    assert_eq!(Ok("line1\nline2".into()), inbound.api("indented").await);
+    assert_eq!(
+        Err(EslError::ApiError("call failed".into())),
+        inbound.api("failed").await
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn corrupt_reply_resyncs() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|command| match command {
+        "api broken" => Some(vec![
+            "Content-Type: api/response\nContent-Length: 1x\n\n+OK garbage\n".to_string(),
+        ]),
+        "api status" => Some(vec![common::api_response("+OK up\n")]),
+        _ => None,
+    })
+    .await?;
+    assert!(matches!(
+        inbound.api("broken").await,
+        Err(EslError::InternalError(_))
+    ));
+    assert_eq!(Ok("up".into()), inbound.api("status").await);
+    assert!(inbound.connected());
+    Ok(())
+}
+
+/// Authenticates as a directory user instead of with the socket password
+#[derive(Debug)]
+struct UserAuth(Option<&'static str>);
+
+impl AuthMethod for UserAuth {
+    fn auth_command(&self, request: &freeswitch_esl::Event) -> Option<String> {
+        assert_eq!(
+            Some("auth/request"),
+            request.headers()["Content-Type"].as_str()
+        );
+        self.0.map(|user| format!("userauth {}", user))
+    }
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn custom_auth_method() -> Result<()> {
+    let (stream, mock) = common::inbound_stream(|command| {
+        command
+            .starts_with("userauth ")
+            .then(|| vec![common::command_reply("+OK accepted")])
+    })
+    .await?;
+    let auth = UserAuth(Some("1000@default:secret"));
+    let inbound = Esl::inbound_with_auth(stream, auth, EslConfig::new()).await?;
+    assert!(inbound.connected());
+    assert_eq!("userauth 1000@default:secret", mock.received()[0]);
+
+    // a socket admitted by its ACL alone gets no auth command
+    let (stream, mock) = common::inbound_stream(|_| None).await?;
+    let inbound = Esl::inbound_with_auth(stream, UserAuth(None), EslConfig::new()).await?;
+    assert!(inbound.connected());
+    assert_eq!(
+        vec!["event json BACKGROUND_JOB CHANNEL_EXECUTE_COMPLETE"],
+        mock.received()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn invalid_event_json_is_skipped() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|command| {
+        (command == "api status").then(|| {
+            vec![
+                "Content-Type: text/event-json\nContent-Length: 0\n\n".to_string(),
+                "Content-Length: 9\nContent-Type: text/event-json\n\nnot json\n".to_string(),
+                common::api_response("+OK up\n"),
+            ]
+        })
+    })
+    .await?;
+    assert_eq!(Ok("up".into()), inbound.api("status").await);
+    assert!(inbound.connected());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn corrupt_non_reply_keeps_pending_command() -> Result<()> {
+    let (inbound, _mock) = common::inbound(|command| match command {
+        "api status" => Some(vec![
+            "Content-Type: log/data\nContent-Length: 1x\n\nlog line\n".to_string(),
+            "Content-Length: 1x\n\n".to_string(),
+            common::api_response("+OK up\n"),
+        ]),
+        "api version" => Some(vec![common::api_response("+OK 1.10\n")]),
+        _ => None,
+    })
+    .await?;
+    // neither frame is a reply, so each command still gets its own
+    assert_eq!(Ok("up".into()), inbound.api("status").await);
+    assert_eq!(Ok("1.10".into()), inbound.api("version").await);
+    assert!(inbound.connected());
+    Ok(())
+}
@@ -0,0 +1,1250 @@
+mod common;
+
+use std::time::Duration;
+
+use anyhow::Result;
+use freeswitch_esl::{
+    AppResponse, BridgeArgs, Esl, EslConfig, EslError, ExecuteResult, IvrMenu, MediaState,
+    OutboundShutdown, PlaybackArgs, PlaybackBuilder, PlaybackEvent, RecordResult, ToAppArgs,
+};
+use ntest::timeout;
+use serde_json::json;
+use tokio_stream::StreamExt;
+
+use common::{execute_complete, outbound};
+
+#[tokio::test]
+#[timeout(10000)]
+async fn playback_result() -> Result<()> {
+    let (conn, _mock) = outbound(|command| {
+        command.contains("execute-app-name: playback").then(|| {
+            execute_complete(
+                command,
+                json!({
+                    "Application-Response": "FILE PLAYED",
+                    "variable_playback_terminator_used": "#",
+                    "variable_playback_ms": "2340",
+                    "variable_playback_samples": "18720",
+                }),
+            )
+        })
+    })
+    .await?;
+    let result = conn
+        .execute_result("playback", "ivr/ivr-welcome.wav")
+        .await?;
+    assert_eq!("playback", result.app_name());
+    let AppResponse::Playback(playback) = result.view() else {
+        panic!("expected playback view, got {:?}", result.view());
+    };
+    assert_eq!(Some("FILE PLAYED".into()), playback.response);
+    assert_eq!(Some("#".into()), playback.terminator);
+    assert_eq!(Some(2340), playback.played_ms);
+    assert_eq!(Some(18720), playback.samples);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn bridge_result() -> Result<()> {
+    let (conn, _mock) = outbound(|command| {
+        command.contains("execute-app-name: bridge").then(|| {
+            execute_complete(
+                command,
+                json!({
+                    "variable_DIALSTATUS": "SUCCESS",
+                    "variable_originate_disposition": "SUCCESS",
+                    "variable_last_bridge_to": "b2b2b2b2-0000-4000-8000-000000000002",
+                    "variable_last_bridge_hangup_cause": "NORMAL_CLEARING",
+                }),
+            )
+        })
+    })
+    .await?;
+    let event = conn.execute("bridge", "user/1001").await?;
+    let result = ExecuteResult::from(event);
+    let AppResponse::Bridge(bridge) = result.view() else {
+        panic!("expected bridge view, got {:?}", result.view());
+    };
+    assert!(bridge.succeeded());
+    assert_eq!(
+        Some("b2b2b2b2-0000-4000-8000-000000000002".into()),
+        bridge.other_leg_uuid
+    );
+    assert_eq!(Some("NORMAL_CLEARING".into()), bridge.hangup_cause);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn unknown_app_result() -> Result<()> {
+    let (conn, _mock) = outbound(|_| None).await?;
+    let result = conn.execute_result("info", "").await?;
+    assert_eq!(AppResponse::Other(Some("_none_".into())), result.view());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn subscribed_events() -> Result<()> {
+    let (conn, _mock) = outbound(|_| None).await?;
+    assert_eq!(
+        vec!["BACKGROUND_JOB", "CHANNEL_EXECUTE_COMPLETE"],
+        conn.subscribed_events()
+    );
+    conn.subscribe(vec!["DTMF", "CHANNEL_HANGUP"]).await?;
+    assert_eq!(
+        vec![
+            "BACKGROUND_JOB",
+            "CHANNEL_EXECUTE_COMPLETE",
+            "DTMF",
+            "CHANNEL_HANGUP"
+        ],
+        conn.subscribed_events()
+    );
+    conn.subscribe_custom(&["sofia::register"]).await?;
+    conn.unsubscribe(["DTMF"]).await?;
+    assert_eq!(
+        vec![
+            "BACKGROUND_JOB",
+            "CHANNEL_EXECUTE_COMPLETE",
+            "CHANNEL_HANGUP",
+            "CUSTOM",
+            "sofia::register"
+        ],
+        conn.subscribed_events()
+    );
+    conn.unsubscribe_all().await?;
+    assert!(conn.subscribed_events().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn rejected_subscribe_keeps_events() -> Result<()> {
+    let (conn, _mock) = outbound(|command| {
+        (command == "event json NOT_AN_EVENT")
+            .then(|| vec![common::command_reply("-ERR no keywords supplied")])
+    })
+    .await?;
+    conn.subscribe(vec!["NOT_AN_EVENT"]).await?;
+    assert_eq!(
+        vec!["BACKGROUND_JOB", "CHANNEL_EXECUTE_COMPLETE"],
+        conn.subscribed_events()
+    );
+    Ok(())
+}
+
+fn ivr_mock(inputs: &'static [Option<&'static str>]) -> impl Fn(&str) -> Option<Vec<String>> {
+    let attempt = std::sync::atomic::AtomicUsize::new(0);
+    move |command| {
+        command
+            .contains("execute-app-name: play_and_get_digits")
+            .then(|| {
+                let attempt = attempt.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                common::digits_entered(command, inputs[attempt])
+            })
+    }
+}
+
+fn played_files(mock: &common::Mock) -> Vec<String> {
+    mock.commands()
+        .iter()
+        .filter(|command| command.contains("execute-app-name: playback"))
+        .filter_map(|command| common::command_header(command, "execute-app-arg"))
+        .map(|file| file.to_string())
+        .collect()
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn ivr_menu_invalid_then_valid() -> Result<()> {
+    let (conn, mock) = outbound(ivr_mock(&[Some("9"), Some("2")])).await?;
+    let menu = IvrMenu::new("ivr/menu.wav")
+        .choice("1", "sales")
+        .choice("2", "support")
+        .invalid_prompt("ivr/invalid.wav");
+    assert_eq!("support", menu.run(&conn).await?);
+    assert_eq!(vec!["ivr/invalid.wav"], played_files(&mock));
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn ivr_menu_runs_out_of_tries() -> Result<()> {
+    let (conn, mock) = outbound(ivr_mock(&[None, Some("7")])).await?;
+    let menu = IvrMenu::new("ivr/menu.wav")
+        .choice("1", 1)
+        .invalid_prompt("ivr/invalid.wav")
+        .timeout_prompt("ivr/timeout.wav")
+        .tries(2);
+    assert_eq!(Err(EslError::NoInput), menu.run(&conn).await);
+    assert_eq!(vec!["ivr/timeout.wav"], played_files(&mock));
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn nomedia_call_command() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    conn.send_call_command("nomedia", &[("nomedia-uuid", common::CALL_UUID)])
+        .await?;
+    assert_eq!(
+        vec![format!(
+            "sendmsg {}\ncall-command: nomedia\nnomedia-uuid: {}",
+            common::CALL_UUID,
+            common::CALL_UUID
+        )],
+        mock.commands()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn rejected_call_command() -> Result<()> {
+    let (conn, _mock) = outbound(|command| {
+        command
+            .contains("call-command: hold-bleg")
+            .then(|| vec![common::command_reply("-ERR invalid command")])
+    })
+    .await?;
+    assert_eq!(
+        Err(EslError::ApiError("invalid command".into())),
+        conn.send_call_command("hold-bleg", &[]).await.map(|_| ())
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn record_session_mask_and_unmask() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    let path = "/recordings/call.wav";
+    conn.record_session(path).await?;
+    conn.record_session_mask(path).await?;
+    conn.record_session_unmask(path).await?;
+    assert_eq!(
+        vec![
+            ("record_session".to_string(), path.to_string()),
+            ("record_session_mask".to_string(), path.to_string()),
+            ("record_session_unmask".to_string(), path.to_string()),
+        ],
+        mock.executed_apps()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn tone_detect_arguments() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    conn.tone_detect(
+        "fax",
+        "1100,2100",
+        "r",
+        30000,
+        "transfer",
+        "fax XML default",
+    )
+    .await?;
+    conn.tone_detect("busy", "480,620", "rw", 0, "hangup", "")
+        .await?;
+    assert_eq!(
+        vec![
+            (
+                "tone_detect".to_string(),
+                "fax 1100,2100 r +30000 transfer fax XML default".to_string()
+            ),
+            (
+                "tone_detect".to_string(),
+                "busy 480,620 rw 0 hangup".to_string()
+            ),
+        ],
+        mock.executed_apps()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn detected_tone_event() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    let mut events = Box::pin(conn.events());
+    mock.push(common::event_json(json!({
+        "Event-Name": "DETECTED_TONE",
+        "Unique-ID": common::CALL_UUID,
+        "Detected-Tone": "fax",
+    })));
+    let event = events.next().await.unwrap();
+    assert_eq!(json!("fax"), event_field(&event, "Detected-Tone"));
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn bridge_on_answer_before_completion() -> Result<()> {
+    let (conn, mock) = outbound(|command| {
+        command.contains("execute-app-name: bridge").then(|| {
+            vec![
+                common::command_reply("+OK"),
+                common::event_json(json!({
+                    "Event-Name": "CHANNEL_BRIDGE",
+                    "Unique-ID": common::CALL_UUID,
+                    "Other-Leg-Unique-ID": "b2b2b2b2-0000-4000-8000-000000000002",
+                })),
+            ]
+        })
+    })
+    .await?;
+    let (answered_tx, answered_rx) = tokio::sync::oneshot::channel();
+    let bridge = conn.bridge_with_on_answer("user/1001", move || {
+        answered_tx.send(()).unwrap();
+    });
+    let hangup = async {
+        answered_rx.await.unwrap();
+        let command = mock.commands().pop().unwrap();
+        for frame in execute_complete(&command, json!({"variable_DIALSTATUS": "SUCCESS"})) {
+            mock.push(frame);
+        }
+    };
+    let (result, _) = tokio::join!(bridge, hangup);
+    let AppResponse::Bridge(bridge) = ExecuteResult::from(result?).view() else {
+        panic!("expected bridge view");
+    };
+    assert!(bridge.succeeded());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn execute_locked() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    conn.execute_locked("playback", "ivr/ivr-welcome.wav")
+        .await?;
+    conn.execute("playback", "ivr/ivr-goodbye.wav").await?;
+    let commands = mock.commands();
+    let sendmsgs: Vec<_> = commands
+        .iter()
+        .filter(|command| command.starts_with("sendmsg"))
+        .collect();
+    assert!(sendmsgs[0].lines().any(|line| line == "event-lock: true"));
+    assert!(!sendmsgs[1].contains("event-lock"));
+    assert_eq!(
+        vec![
+            ("playback".to_string(), "ivr/ivr-welcome.wav".to_string()),
+            ("playback".to_string(), "ivr/ivr-goodbye.wav".to_string()),
+        ],
+        mock.executed_apps()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn answer_media_state() -> Result<()> {
+    let (conn, mock) = outbound(|command| {
+        let state = if command.contains("execute-app-name: pre_answer") {
+            json!({"Channel-Call-State": "EARLY", "Answer-State": "early"})
+        } else if command.contains("execute-app-name: answer") {
+            json!({"Channel-Call-State": "ACTIVE", "Answer-State": "answered"})
+        } else if command.contains("execute-app-name: sleep") {
+            json!({"Answer-State": "early"})
+        } else {
+            return None;
+        };
+        Some(execute_complete(command, state))
+    })
+    .await?;
+    assert_eq!(MediaState::Early, conn.pre_answer_with_state().await?);
+    assert_eq!(MediaState::Active, conn.answer_with_state().await?);
+    let sleep = conn.execute_result("sleep", "100").await?;
+    assert_eq!(Some(MediaState::Early), sleep.media_state());
+    assert_eq!(
+        vec![
+            ("pre_answer".to_string(), String::new()),
+            ("answer".to_string(), String::new()),
+            ("sleep".to_string(), "100".to_string()),
+        ],
+        mock.executed_apps()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn dtmf_buffer() -> Result<()> {
+    let config = EslConfig::new().dtmf_buffer(4);
+    let (conn, mock) = common::outbound_with_config(|_| None, config).await;
+    let conn = conn?;
+    let mut events = Box::pin(conn.events_filtered(&["DTMF"]));
+    let dtmf = |digit: &str| {
+        common::event_json(json!({
+            "Event-Name": "DTMF",
+            "Unique-ID": common::CALL_UUID,
+            "DTMF-Digit": digit,
+            "DTMF-Duration": "2000",
+            "DTMF-Source": "RTP",
+        }))
+    };
+    for digit in ["1", "2", "#"] {
+        mock.push(dtmf(digit));
+    }
+    mock.push(common::event_json(json!({
+        "Event-Name": "CHANNEL_STATE",
+        "Unique-ID": common::CALL_UUID,
+    })));
+    for _ in 0..3 {
+        events.next().await.unwrap();
+    }
+    assert_eq!("12#", conn.peek_dtmf());
+    assert_eq!("12#", conn.drain_dtmf());
+    assert_eq!("", conn.peek_dtmf());
+    mock.push(dtmf("9"));
+    events.next().await.unwrap();
+    assert_eq!("9", conn.drain_dtmf());
+    // only the last 4 digits are kept
+    for digit in ["1", "2", "3", "4", "5"] {
+        mock.push(dtmf(digit));
+    }
+    for _ in 0..5 {
+        events.next().await.unwrap();
+    }
+    assert_eq!("2345", conn.drain_dtmf());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn dtmf_buffer_disabled_by_default() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    let mut events = Box::pin(conn.events_filtered(&["DTMF"]));
+    mock.push(common::event_json(json!({
+        "Event-Name": "DTMF",
+        "Unique-ID": common::CALL_UUID,
+        "DTMF-Digit": "1",
+    })));
+    events.next().await.unwrap();
+    assert_eq!("", conn.peek_dtmf());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn bridge_to_with_variables() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    conn.bridge_to(
+        "sofia/gateway/carrier/15551234567",
+        &[
+            ("origination_caller_id_name", "Sales, EMEA"),
+            ("absolute_codec_string", "PCMU,PCMA"),
+            ("call_timeout", "30"),
+        ],
+    )
+    .await?;
+    conn.bridge_to("user/1001", &[]).await?;
+    assert_eq!(
+        vec![
+            (
+                "bridge".to_string(),
+                "{origination_caller_id_name='Sales, EMEA',absolute_codec_string='PCMU,PCMA',call_timeout=30}sofia/gateway/carrier/15551234567".to_string()
+            ),
+            ("bridge".to_string(), "user/1001".to_string()),
+        ],
+        mock.executed_apps()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn playback_events() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    conn.subscribe(vec!["PLAYBACK_START", "PLAYBACK_STOP"])
+        .await?;
+    let mut events = Box::pin(conn.events_filtered(&["PLAYBACK_START", "PLAYBACK_STOP"]));
+    mock.push(common::event_json(json!({
+        "Event-Name": "PLAYBACK_START",
+        "Unique-ID": common::CALL_UUID,
+        "Playback-File-Path": "/var/lib/freeswitch/recordings/briefing.wav",
+        "variable_current_application": "playback",
+        "variable_playback_samples": "8000",
+        "variable_playback_ms": "1000",
+    })));
+    mock.push(common::event_json(json!({
+        "Event-Name": "PLAYBACK_STOP",
+        "Unique-ID": common::CALL_UUID,
+        "Playback-File-Path": "/var/lib/freeswitch/recordings/briefing.wav",
+        "Playback-Status": "break",
+        "variable_current_application": "playback",
+        "variable_playback_samples": "1442560",
+        "variable_playback_ms": "180320",
+    })));
+    let start = PlaybackEvent::try_from(&events.next().await.unwrap())?;
+    assert_eq!(
+        PlaybackEvent {
+            uuid: Some(common::CALL_UUID.into()),
+            file: Some("/var/lib/freeswitch/recordings/briefing.wav".into()),
+            stopped: false,
+            status: None,
+            samples: None,
+            ms: None,
+        },
+        start
+    );
+    let stop = PlaybackEvent::try_from(&events.next().await.unwrap())?;
+    assert!(stop.stopped);
+    assert_eq!(Some("break".into()), stop.status);
+    assert_eq!(Some(1442560), stop.samples);
+    assert_eq!(Some(180320), stop.ms);
+    Ok(())
+}
+
+fn event_field(event: &freeswitch_esl::Event, key: &str) -> serde_json::Value {
+    let body: serde_json::Value = serde_json::from_str(event.body().as_ref().unwrap()).unwrap();
+    body[key].clone()
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn playback_terminators() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    assert_eq!(None, conn.playback_terminators());
+    conn.set_playback_terminators("#").await?;
+    assert_eq!(Some("#".into()), conn.playback_terminators());
+    conn.set_playback_terminators("none").await?;
+    assert_eq!(Some("none".into()), conn.playback_terminators());
+    assert_eq!(
+        vec![
+            ("set".to_string(), "playback_terminators=#".to_string()),
+            ("set".to_string(), "playback_terminators=none".to_string()),
+        ],
+        mock.executed_apps()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn events_filtered_by_name() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    let mut events = Box::pin(conn.events_filtered(&["DTMF", "CHANNEL_HANGUP"]));
+    for name in ["CHANNEL_STATE", "DTMF", "HEARTBEAT", "CHANNEL_HANGUP"] {
+        mock.push(common::event_json(json!({
+            "Event-Name": name,
+            "Unique-ID": common::CALL_UUID,
+        })));
+    }
+    let first = events.next().await.unwrap();
+    let second = events.next().await.unwrap();
+    assert_eq!(Some("DTMF".into()), first.event_name());
+    assert_eq!(Some("CHANNEL_HANGUP".into()), second.event_name());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn event_before_connect_reply() -> Result<()> {
+    let (conn, _mock) = outbound(|command| {
+        (command == "connect").then(|| {
+            let body = format!(
+                "Event-Name: CHANNEL_PARK\nUnique-ID: {}\nCaller-Caller-ID-Name: Extension%201000\n\n",
+                common::CALL_UUID
+            );
+            let early = format!(
+                "Content-Length: {}\nContent-Type: text/event-plain\n\n{}",
+                body.len(),
+                body
+            );
+            [vec![early], common::default_reply(command)].concat()
+        })
+    })
+    .await?;
+    assert_eq!(Some(common::CALL_UUID.into()), conn.call_uuid().await);
+    assert_eq!(
+        Some("Extension 1000".into()),
+        conn.connection_info().unwrap().caller_id_name
+    );
+    // internal event waits must leave the setup events to the caller
+    let _ = conn.wait_for_progress(Duration::from_millis(10)).await;
+    let early = conn.take_early_events();
+    assert_eq!(1, early.len());
+    let early = &early[0];
+    assert_eq!(Some("CHANNEL_PARK".into()), early.event_name());
+    assert_eq!(
+        Some("Extension 1000"),
+        early.headers()["Caller-Caller-ID-Name"].as_str()
+    );
+    assert!(conn.take_early_events().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn connection_info() -> Result<()> {
+    let (conn, _mock) = outbound(|_| None).await?;
+    let info = conn.connection_info().unwrap();
+    assert_eq!(
+        Some("sofia/internal/1000@172.31.32.63:5060".into()),
+        info.channel_name
+    );
+    assert_eq!(Some(common::CALL_UUID.into()), info.unique_id);
+    assert_eq!(Some("inbound".into()), info.direction);
+    assert_eq!(Some("Extension 1000".into()), info.caller_id_name);
+    assert_eq!(Some("1000".into()), info.caller_id_number);
+    assert_eq!(Some("5000".into()), info.destination_number);
+    assert_eq!(Some("default".into()), info.context);
+    assert_eq!(Some("CS_EXECUTE".into()), info.channel_state);
+    assert_eq!(Some(&json!("async")), info.raw().get("Socket-Mode"));
+    assert_eq!(Some("XML".into()), conn.get_info_string("Caller-Dialplan"));
+    assert_eq!(
+        Some("2023-09-12 04:31:37".into()),
+        conn.get_info_string("Event-Date-Local")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn failed_app_completion() -> Result<()> {
+    let (conn, _mock) = outbound(|command| {
+        command.contains("execute-app-name: transfer").then(|| {
+            execute_complete(
+                command,
+                json!({"Application-Response": "-ERR invalid destination"}),
+            )
+        })
+    })
+    .await?;
+    assert_eq!(
+        Err(EslError::AppError("transfer: invalid destination".into())),
+        conn.execute("transfer", "").await
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn rejected_app() -> Result<()> {
+    let (conn, _mock) = outbound(|command| {
+        command
+            .contains("execute-app-name: no_such_app")
+            .then(|| vec![common::command_reply("-ERR invalid application")])
+    })
+    .await?;
+    assert_eq!(
+        Err(EslError::AppError(
+            "no_such_app: invalid application".into()
+        )),
+        conn.execute("no_such_app", "").await
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn successful_app_completion() -> Result<()> {
+    let (conn, _mock) = outbound(|command| {
+        command
+            .contains("execute-app-name: playback")
+            .then(|| execute_complete(command, json!({"Application-Response": "FILE PLAYED"})))
+    })
+    .await?;
+    let event = conn.execute("playback", "ivr/ivr-welcome.wav").await?;
+    assert_eq!(
+        json!("FILE PLAYED"),
+        event_field(&event, "Application-Response")
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn progress_with_and_without_media() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    let push_progress = |name: &str| {
+        mock.push(common::event_json(json!({
+            "Event-Name": name,
+            "Unique-ID": common::CALL_UUID,
+            "Answer-State": if name == "CHANNEL_PROGRESS" { "ringing" } else { "early" },
+        })))
+    };
+    let (ringing, _) = tokio::join!(conn.wait_for_progress(Duration::from_secs(5)), async {
+        push_progress("CHANNEL_PROGRESS")
+    });
+    let ringing = ringing?.unwrap();
+    assert!(!ringing.media);
+    assert_eq!(Some("ringing".into()), ringing.answer_state);
+    let (early, _) = tokio::join!(conn.wait_for_progress(Duration::from_secs(5)), async {
+        push_progress("CHANNEL_PROGRESS_MEDIA")
+    });
+    let early = early?.unwrap();
+    assert!(early.media);
+    assert_eq!(Some(common::CALL_UUID.into()), early.uuid);
+    assert_eq!(
+        None,
+        conn.wait_for_progress(Duration::from_millis(50)).await?
+    );
+    let (closed, _) = tokio::join!(conn.wait_for_progress(Duration::from_secs(5)), async {
+        mock.close()
+    });
+    assert_eq!(Err(EslError::Disconnected), closed);
+    Ok(())
+}
+
+#[test]
+fn playback_builder_file_string() {
+    let prompt = "ivr/ivr-welcome.wav";
+    let cases = [
+        (PlaybackBuilder::new(prompt), "ivr/ivr-welcome.wav"),
+        (
+            PlaybackBuilder::new(prompt).gain_db(-6),
+            "file_string://{vol=-6}ivr/ivr-welcome.wav",
+        ),
+        (
+            PlaybackBuilder::new(prompt).prepend_silence_ms(500),
+            "file_string://silence_stream://500!ivr/ivr-welcome.wav",
+        ),
+        (
+            PlaybackBuilder::new(prompt).append_silence_ms(250),
+            "file_string://ivr/ivr-welcome.wav!silence_stream://250",
+        ),
+        (
+            PlaybackBuilder::new(prompt).loops(2),
+            "file_string://ivr/ivr-welcome.wav!ivr/ivr-welcome.wav",
+        ),
+        (PlaybackBuilder::new(prompt).loops(0), prompt),
+        (
+            PlaybackBuilder::new(prompt)
+                .gain_db(3)
+                .prepend_silence_ms(500)
+                .append_silence_ms(250)
+                .loops(2),
+            "file_string://silence_stream://500!{vol=3}ivr/ivr-welcome.wav!{vol=3}ivr/ivr-welcome.wav!silence_stream://250",
+        ),
+    ];
+    for (builder, expected) in cases {
+        assert_eq!(expected, builder.build());
+    }
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn playback_builder_play() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    PlaybackBuilder::new("ivr/ivr-welcome.wav")
+        .prepend_silence_ms(500)
+        .play(&conn)
+        .await?;
+    assert_eq!(
+        vec![(
+            "playback".to_string(),
+            "file_string://silence_stream://500!ivr/ivr-welcome.wav".to_string()
+        )],
+        mock.executed_apps()
+    );
+    Ok(())
+}
+
+fn dead_channel(command: &str) -> Option<Vec<String>> {
+    (command.starts_with("event json") || command == "myevents")
+        .then(|| vec![common::command_reply("-ERR Channel already hung up")])
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn failed_setup() -> Result<()> {
+    let (conn, _mock) = common::outbound_with_config(dead_channel, EslConfig::new()).await;
+    assert_eq!(
+        EslError::ApiError("Channel already hung up".into()),
+        conn.unwrap_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn connect_reply_without_unique_id() -> Result<()> {
+    let handler = |command: &str| {
+        (command == "connect").then(|| vec![common::command_reply("+OK")])
+    };
+    let (conn, _mock) = common::outbound_with_config(handler, EslConfig::new()).await;
+    assert_eq!(
+        EslError::InternalError("connect reply without Channel-Unique-ID".into()),
+        conn.unwrap_err()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn lenient_setup_allows_hangup() -> Result<()> {
+    let config = EslConfig::new().lenient_setup(true);
+    let (conn, mock) = common::outbound_with_config(dead_channel, config).await;
+    let conn = conn?;
+    assert_eq!(Some(common::CALL_UUID.into()), conn.call_uuid().await);
+    conn.hangup("NORMAL_CLEARING").await?;
+    assert_eq!(
+        vec![("hangup".to_string(), "NORMAL_CLEARING".to_string())],
+        mock.executed_apps()
+    );
+    Ok(())
+}
+
+#[test]
+fn typed_app_args() {
+    let cases: Vec<(Box<dyn ToAppArgs>, &str, &str)> = vec![
+        (
+            Box::new(PlaybackArgs::new("ivr/ivr-welcome.wav")),
+            "playback",
+            "ivr/ivr-welcome.wav",
+        ),
+        (
+            Box::new(PlaybackBuilder::new("ivr/ivr-welcome.wav").loops(2)),
+            "playback",
+            "file_string://ivr/ivr-welcome.wav!ivr/ivr-welcome.wav",
+        ),
+        (Box::new(BridgeArgs::new("user/1000")), "bridge", "user/1000"),
+        (
+            Box::new(BridgeArgs::new("user/1000").endpoint("user/1001")),
+            "bridge",
+            "user/1000,user/1001",
+        ),
+        (
+            Box::new(
+                BridgeArgs::new("user/1000")
+                    .endpoint("sofia/gateway/carrier/5551234")
+                    .sequential(true)
+                    .variable("call_timeout", "20")
+                    .variable("origination_caller_id_name", "Support, Inc"),
+            ),
+            "bridge",
+            "{call_timeout=20,origination_caller_id_name='Support, Inc'}user/1000|sofia/gateway/carrier/5551234",
+        ),
+    ];
+    for (args, app_name, app_args) in cases {
+        assert_eq!(app_name, args.app_name());
+        assert_eq!(app_args, args.to_app_args());
+    }
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn execute_typed() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    conn.execute_typed(BridgeArgs::new("user/1000").variable("call_timeout", "20"))
+        .await?;
+    assert_eq!(
+        vec![(
+            "bridge".to_string(),
+            "{call_timeout=20}user/1000".to_string()
+        )],
+        mock.executed_apps()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn drain_outbound_server() -> Result<()> {
+    let listener = tokio::net::TcpListener::bind("localhost:0").await?;
+    let addr = listener.local_addr()?;
+    let shutdown = OutboundShutdown::new();
+    let (release, released) = tokio::sync::watch::channel(false);
+    let server = tokio::spawn(Esl::serve_outbound(
+        listener,
+        EslConfig::default(),
+        shutdown.clone(),
+        move |conn| {
+            let mut released = released.clone();
+            async move {
+                released.wait_for(|released| *released).await.unwrap();
+                conn.answer().await.unwrap();
+            }
+        },
+    ));
+    let mock = common::call(addr, |_| None).await?;
+    while shutdown.in_flight() == 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    shutdown.drain(Duration::from_secs(5));
+    while tokio::net::TcpStream::connect(addr).await.is_ok() {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(1, shutdown.in_flight());
+    assert!(!server.is_finished());
+
+    release.send(true)?;
+    server.await??;
+    assert_eq!(0, shutdown.in_flight());
+    assert_eq!("answer", mock.executed_apps()[0].0);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn playback_url() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    conn.playback_url("https://prompts.example.com/welcome.wav")
+        .await?;
+    conn.playback_url("shout://radio.example.com/stream.mp3")
+        .await?;
+    conn.playback_url_cached("http://prompts.example.com/menu.wav")
+        .await?;
+    conn.playback_url_cached("http_cache://http://prompts.example.com/menu.wav")
+        .await?;
+    for url in [
+        "/tmp/welcome.wav",
+        "ftp://example.com/a.wav",
+        "http://",
+        "http://a b.wav",
+    ] {
+        assert!(matches!(
+            conn.playback_url(url).await,
+            Err(EslError::InvalidArgument(_))
+        ));
+    }
+    assert!(matches!(
+        conn.playback_url_cached("shout://radio.example.com/stream.mp3")
+            .await,
+        Err(EslError::InvalidArgument(_))
+    ));
+    let played: Vec<String> = mock
+        .executed_apps()
+        .into_iter()
+        .map(|(app, args)| format!("{} {}", app, args))
+        .collect();
+    assert_eq!(
+        vec![
+            "playback https://prompts.example.com/welcome.wav",
+            "playback shout://radio.example.com/stream.mp3",
+            "playback http_cache://http://prompts.example.com/menu.wav",
+            "playback http_cache://http://prompts.example.com/menu.wav",
+        ],
+        played
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn record_with_beep_terminated() -> Result<()> {
+    let (conn, mock) = outbound(|command| {
+        command.contains("execute-app-name: record").then(|| {
+            execute_complete(
+                command,
+                json!({
+                    "Application-Response": "_none_",
+                    "variable_playback_terminator_used": "#",
+                    "variable_record_ms": "4820",
+                    "variable_record_samples": "38560",
+                }),
+            )
+        })
+    })
+    .await?;
+    let result = conn
+        .record_with_beep("tone_stream://%(500,0,800)", "/tmp/vm.wav", 120, 5, "#")
+        .await?;
+    assert_eq!(
+        RecordResult {
+            response: Some("_none_".into()),
+            terminator: Some("#".into()),
+            duration_ms: Some(4820),
+        },
+        result
+    );
+    assert_eq!(
+        vec![
+            (
+                "playback".to_string(),
+                "tone_stream://%(500,0,800)".to_string()
+            ),
+            ("set".to_string(), "playback_terminators=#".to_string()),
+            ("record".to_string(), "/tmp/vm.wav 120 200 5".to_string()),
+        ],
+        mock.executed_apps()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn long_app_arg_in_body() -> Result<()> {
+    let text = format!("say:{}", "please hold while we connect you ".repeat(40));
+    let (conn, mock) = outbound(|_| None).await?;
+    conn.execute("speak", &text).await?;
+    conn.execute("speak", "say:short").await?;
+    assert_eq!(
+        "+OK",
+        conn.send_call_command("nomedia", &[])
+            .await?
+            .reply_text()
+            .unwrap()
+    );
+    // one reply per message: the body is framed by its exact length and the codec's
+    // closing blank line isn't taken for another command
+    let commands = mock.commands();
+    assert_eq!(3, commands.len());
+    let (headers, body) = commands[0].split_once("\n\n").unwrap();
+    assert!(headers.contains("content-type: text/plain"));
+    assert!(headers.contains(&format!("content-length: {}", text.len())));
+    assert!(!headers.contains("execute-app-arg"));
+    assert_eq!(text, body);
+    assert_eq!(
+        vec![
+            ("speak".to_string(), String::new()),
+            ("speak".to_string(), "say:short".to_string())
+        ],
+        mock.executed_apps()
+    );
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn wait_for_dtmf() -> Result<()> {
+    let (conn, mock) = outbound(|_| None).await?;
+    let (digit, _) = tokio::join!(conn.wait_for_dtmf(Duration::from_secs(5)), async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        mock.push(common::event_json(json!({
+            "Event-Name": "CHANNEL_STATE",
+            "Unique-ID": common::CALL_UUID,
+        })));
+        mock.push(common::event_json(json!({
+            "Event-Name": "DTMF",
+            "Unique-ID": common::CALL_UUID,
+            "DTMF-Digit": "5",
+        })));
+    });
+    assert_eq!(Some('5'), digit?);
+    assert_eq!(None, conn.wait_for_dtmf(Duration::from_millis(100)).await?);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn execute_with_variable() -> Result<()> {
+    let (conn, mock) = outbound(|command| {
+        command.contains("execute-app-name: read").then(|| {
+            execute_complete(
+                command,
+                json!({
+                    "variable_read_result": "success",
+                    "variable_account_pin": "4821",
+                }),
+            )
+        })
+    })
+    .await?;
+    let (event, pin) = conn
+        .execute_with_variable(
+            "read",
+            "4 4 ivr/ivr-please_enter_pin_followed_by_pound.wav account_pin 5000 #",
+            "account_pin",
+        )
+        .await?;
+    assert_eq!(Some("4821".into()), pin);
+    assert_eq!(Some("CHANNEL_EXECUTE_COMPLETE".into()), event.event_name());
+    let (_, missing) = conn
+        .execute_with_variable("read", "4 4 silence_stream://100 other_pin 100 #", "unset")
+        .await?;
+    assert_eq!(None, missing);
+    assert_eq!(2, mock.executed_apps().len());
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn execute_detached_then_await() -> Result<()> {
+    let (conn, mock) = outbound(|command| {
+        command
+            .contains("async: true")
+            .then(|| vec![common::command_reply("+OK")])
+    })
+    .await?;
+    let execution = conn
+        .execute_detached("playback", "local_stream://moh")
+        .await?;
+    // the connection stays usable while the application runs
+    conn.execute("log", "INFO waiting").await?;
+    let detached = mock
+        .commands()
+        .into_iter()
+        .find(|command| command.contains("async: true"))
+        .unwrap();
+    assert!(detached.contains(&format!("Event-UUID: {}", execution.event_uuid())));
+    for frame in execute_complete(&detached, json!({"Application-Response": "FILE PLAYED"})) {
+        mock.push(frame);
+    }
+    let result = ExecuteResult::from(execution.wait().await?);
+    assert_eq!(
+        Some("FILE PLAYED".into()),
+        result.get("Application-Response")
+    );
+    assert_eq!(0, conn.stats().await.pending_jobs);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn execute_detached_forgotten() -> Result<()> {
+    let (conn, _mock) = outbound(|command| {
+        command
+            .contains("async: true")
+            .then(|| vec![common::command_reply("+OK")])
+    })
+    .await?;
+    let execution = conn
+        .execute_detached("playback", "local_stream://moh")
+        .await?;
+    assert_eq!(1, conn.stats().await.pending_jobs);
+    // a wait which is given up doesn't leave its job behind
+    let wait = execution.wait();
+    assert!(tokio::time::timeout(Duration::from_millis(10), wait)
+        .await
+        .is_err());
+    assert_eq!(0, conn.stats().await.pending_jobs);
+
+    let execution = conn
+        .execute_detached("playback", "local_stream://moh")
+        .await?;
+    assert_eq!(1, conn.stats().await.pending_jobs);
+    drop(execution);
+    assert_eq!(0, conn.stats().await.pending_jobs);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn playback_handle_stop() -> Result<()> {
+    let playing = std::sync::Mutex::new(None);
+    let (conn, mock) = outbound(move |command| {
+        if command.contains("execute-app-name: playback") {
+            *playing.lock().unwrap() = Some(command.to_string());
+            return Some(vec![common::command_reply("+OK")]);
+        }
+        if command.starts_with("api uuid_break") {
+            let playback = playing.lock().unwrap().take()?;
+            let mut frames = vec![common::api_response("+OK\n")];
+            frames.extend(execute_complete(
+                &playback,
+                json!({"Application-Response": "FILE PLAYED"}),
+            ));
+            return Some(frames);
+        }
+        None
+    })
+    .await?;
+    let (handle, finished) = conn.playback_handle("local_stream://moh").await?;
+    let mut finished = Box::pin(finished);
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), &mut finished)
+            .await
+            .is_err()
+    );
+    handle.stop().await?;
+    let event = finished.await?;
+    assert_eq!(Some("CHANNEL_EXECUTE_COMPLETE".into()), event.event_name());
+    assert!(mock
+        .commands()
+        .contains(&format!("api uuid_break {}", common::CALL_UUID)));
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn playback_handle_dropped() -> Result<()> {
+    let (conn, _mock) = outbound(|command| {
+        command
+            .contains("execute-app-name: playback")
+            .then(|| vec![common::command_reply("+OK")])
+    })
+    .await?;
+    let (_handle, finished) = conn.playback_handle("local_stream://moh").await?;
+    assert_eq!(1, conn.stats().await.pending_jobs);
+    drop(finished);
+    assert_eq!(0, conn.stats().await.pending_jobs);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn with_max_duration_hangs_up_stuck_app() -> Result<()> {
+    let (conn, mock) = outbound(|command| {
+        if command.contains("execute-app-name: park") {
+            return Some(vec![common::command_reply("+OK")]);
+        }
+        let reply = match command.split(' ').nth(1)? {
+            "sched_api" => "+OK Added: 7\n",
+            // the hangup already fired, so there is nothing to cancel
+            "sched_del" => "-ERR No such task\n",
+            _ => return None,
+        };
+        Some(vec![common::api_response(reply)])
+    })
+    .await?;
+    let stuck = conn.with_max_duration(30, conn.execute("park", ""));
+    let fire = async {
+        let park = loop {
+            let parked = mock
+                .commands()
+                .into_iter()
+                .find(|command| command.contains("execute-app-name: park"));
+            if let Some(park) = parked {
+                break park;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        };
+        // simulates the scheduled hangup ending the application
+        for frame in execute_complete(&park, json!({"variable_hangup_cause": "ALLOTTED_TIMEOUT"})) {
+            mock.push(frame);
+        }
+    };
+    let (result, _) = tokio::join!(stuck, fire);
+    let result = ExecuteResult::from(result?);
+    assert_eq!(
+        Some("ALLOTTED_TIMEOUT".into()),
+        result.get("variable_hangup_cause")
+    );
+    let commands = mock.commands();
+    assert_eq!(
+        format!(
+            "api sched_api +30 none uuid_kill {} ALLOTTED_TIMEOUT",
+            common::CALL_UUID
+        ),
+        commands[0]
+    );
+    assert!(commands[1].contains("execute-app-name: park"));
+    // only the task of this hangup is removed, not all tasks of the call
+    assert_eq!("api sched_del 7", commands[2]);
+    Ok(())
+}
+
+#[tokio::test]
+#[timeout(10000)]
+async fn with_max_duration_dropped_cancels_hangup() -> Result<()> {
+    let (conn, mock) = outbound(|command| {
+        if command.contains("execute-app-name: park") {
+            return Some(vec![common::command_reply("+OK")]);
+        }
+        let reply = match command.split(' ').nth(1)? {
+            "sched_api" => "+OK Added: 7\n",
+            "sched_del" => "+OK Deleted: 7\n",
+            _ => return None,
+        };
+        Some(vec![common::api_response(reply)])
+    })
+    .await?;
+    let stuck = conn.with_max_duration(30, conn.execute("park", ""));
+    assert!(tokio::time::timeout(Duration::from_millis(50), stuck)
+        .await
+        .is_err());
+    while !mock.commands().contains(&"api sched_del 7".to_string()) {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    // the connection still correlates replies after the detached sched_del
+    assert_eq!(Ok("Deleted: 7".into()), conn.api("sched_del 7").await);
+    Ok(())
+}